@@ -1,8 +1,18 @@
 mod server;
 
+use common::game::GameSettings;
 use futures::executor::block_on;
 use server::start_server;
 
 fn main() {
-    block_on(start_server());
+    let settings = match GameSettings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(error) => {
+            println!("{}", error);
+            println!("Usage: tictactoe-async [players] [width] [height] [win length] or a variant name like ultimate, add time=300+2 for clocks");
+            return;
+        },
+    };
+
+    block_on(start_server(settings.hosted()));
 }
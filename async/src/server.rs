@@ -1,27 +1,38 @@
-use std::{collections::{HashMap, HashSet}, net::SocketAddr, sync::{Arc, Mutex}};
+use std::{collections::HashMap, net::SocketAddr, sync::{Arc, Mutex}, time::Duration};
 
 use async_std::{net::{TcpListener, TcpStream}};
 use async_std::task;
-use common::{game::{Game, GameResult, Mark, NUM_PLAYERS, Player}, message::{ReceiveMessage, SendMessage}};
+use common::{game::{Game, GameResult, GameSettings, Mark, MoveError, Player, RequestError, Square, Variant}, message::{LiveGame, ReceiveMessage, SendMessage}, rules::Rules, series::Match};
 use futures::{StreamExt, TryStreamExt, channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded}, future};
 use async_tungstenite::tungstenite::protocol::Message;
 
+/// How often the clocks of every game are checked
+const CLOCK_CHECK: Duration = Duration::from_millis(100);
+
 /// A server
 pub struct Server {
     messages: HashMap<SocketAddr, UnboundedSender<Message>>,
-    lobby: HashSet<SocketAddr>,
-    games: Vec<Game<SocketAddr>>,
+    lobby: HashMap<SocketAddr, Variant>, //id to the variant they're waiting to play
+    games: Vec<Game<SocketAddr, Box<dyn Rules>>>,
     game_map: HashMap<SocketAddr, usize>, //id to index of games
+    settings: Vec<GameSettings>, //settings for each variant hosted
+    matches: HashMap<usize, Match>, //index of a game to the match it's part of
+    spectators: HashMap<usize, Vec<SocketAddr>>, //index of a game to the users watching it
+    watching: HashMap<SocketAddr, usize>, //id to index of the game they're watching
 }
 
 impl Server {
-    /// Creates a new server
-    pub fn new() -> Self {
+    /// Creates a new server that hosts a game for each of the given settings
+    pub fn new(settings: Vec<GameSettings>) -> Self {
         Server {
             messages: HashMap::new(),
-            lobby: HashSet::new(),
+            lobby: HashMap::new(),
             games: Vec::new(),
             game_map: HashMap::new(),
+            settings,
+            matches: HashMap::new(),
+            spectators: HashMap::new(),
+            watching: HashMap::new(),
         }
     }
 
@@ -35,32 +46,30 @@ impl Server {
         }
     }
 
-    /// Sends a message to all users in a server/game
-    pub fn send_all(&self, msg: Message, game_index: Option<usize>) {
-        match game_index {
-            Some(index) => {
-                let game = &self.games[index];
-                for addr in game.get_player_ids() {
-                    self.send_one(addr, msg.clone());
-                }
-            },
-            None => {
-                for sender in self.messages.values() {
-                    sender.unbounded_send(msg.clone()).unwrap();
-                }
-            },
-        }
+    /// Sends an error back to a user whose move couldn't be made
+    pub fn send_error(&self, addr: SocketAddr, error: MoveError) {
+        let message = SendMessage::Error {error};
+        self.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
     }
 
-    /// Sends a message to all user except one in a server/game
-    pub fn send_all_but_one(&self, msg: Message, addr_not_send: SocketAddr, game_index: Option<usize>) {
+    /// Sends an error back to a user whose request, like a draw offer or rematch, couldn't be made
+    pub fn send_rejected(&self, addr: SocketAddr, error: RequestError) {
+        let message = SendMessage::Rejected {error};
+        self.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
+    }
+
+    /// Gets the settings games of a variant are played with, None if the server doesn't host it
+    pub fn variant_settings(&self, variant: Variant) -> Option<GameSettings> {
+        self.settings.iter().find(|settings| settings.variant == variant).copied()
+    }
+
+    /// Sends a message to all users in a server/game, including anyone watching the game
+    pub fn send_all(&self, msg: Message, game_index: Option<usize>) {
         match game_index {
             Some(index) => {
                 let game = &self.games[index];
-                for addr in game.get_player_ids() {
-                    if addr != addr_not_send {
-                        self.send_one(addr, msg.clone());
-                    }
+                for addr in game.get_player_ids().into_iter().chain(self.game_spectators(index)) {
+                    self.send_one(addr, msg.clone());
                 }
             },
             None => {
@@ -73,46 +82,71 @@ impl Server {
 
     /// Disconnects a user from the lobby/a game
     pub fn disconnect(&mut self, addr: SocketAddr) {
-        let removed = self.lobby.remove(&addr);
-        if !removed {
-            let game_index = *self.game_map.get(&addr).unwrap();
-
-            //send to all other players in game that the player disconnected
+        if self.lobby.remove(&addr).is_some() {
+            println!("User {} removed from lobby", addr);
+        } else if let Some(game_index) = self.game_map.get(&addr).copied() {
             println!("User {} left game", addr);
+            self.leave_game(addr, game_index);
+        }
+        self.stop_watching(addr);
 
-            let message_str = serde_json::to_string(&SendMessage::PlayerLeft).unwrap();
-            for player_addr in self.games[game_index].get_player_ids().iter() {
-                if player_addr != &addr {
-                    if let Some(sender) = self.messages.get(&player_addr) {
-                        sender.unbounded_send(Message::Text(message_str.clone())).unwrap();
-                    }
-                }
+        self.messages.remove(&addr);
+    }
+
+    /// Gets the users watching a game
+    pub fn game_spectators(&self, game_index: usize) -> Vec<SocketAddr> {
+        self.spectators.get(&game_index).cloned().unwrap_or_default()
+    }
+
+    /// Stops a user watching the game they're watching, if they are
+    pub fn stop_watching(&mut self, addr: SocketAddr) {
+        if let Some(game_index) = self.watching.remove(&addr) {
+            if let Some(spectators) = self.spectators.get_mut(&game_index) {
+                spectators.retain(|spectator| *spectator != addr);
             }
-            self.games[game_index].player_left();
-        } else {
-            println!("User {} removed from lobby", addr);
         }
+    }
 
-        self.messages.remove(&addr);
+    /// Takes a user out of a game, ending it if it's still going
+    pub fn leave_game(&mut self, addr: SocketAddr, game_index: usize) {
+        self.game_map.remove(&addr);
+
+        //send to the other players still in the game and anyone watching that the player left
+        let message_str = serde_json::to_string(&SendMessage::PlayerLeft).unwrap();
+        let still_in = self.games[game_index].get_player_ids().into_iter()
+            .filter(|player_addr| self.game_map.get(player_addr) == Some(&game_index))
+            .chain(self.game_spectators(game_index))
+            .collect::<Vec<_>>();
+        for player_addr in still_in {
+            if let Some(sender) = self.messages.get(&player_addr) {
+                sender.unbounded_send(Message::Text(message_str.clone())).unwrap();
+            }
+        }
+        self.games[game_index].player_left();
+        //a match can't go on without everyone
+        self.matches.remove(&game_index);
     }
 }
 
 /// Starts a server
-pub async fn start_server() {
+pub async fn start_server(settings: Vec<GameSettings>) {
     let try_socket = TcpListener::bind("127.0.0.1:8000").await;
     let listener = try_socket.expect("Failed to bind");
 
-    let server = Server::new();
+    let server = Server::new(settings);
     let server_arc = Arc::new(Mutex::new(server));
 
+    //clocks have to run out even when nobody sends anything
+    let clock_server = server_arc.clone();
+    task::spawn(async move {
+        loop {
+            task::sleep(CLOCK_CHECK).await;
+            check_flags(&mut clock_server.lock().unwrap());
+        }
+    });
+
     while let Ok((stream, addr)) = listener.accept().await {
         let server_clone = server_arc.clone();
-
-        //do server new client stuff
-        let mut server = server_clone.lock().unwrap();
-        server.lobby.insert(addr);
-        drop(server);
-
         task::spawn(handle_connection(stream, addr, server_clone));
     }
 }
@@ -130,11 +164,11 @@ async fn handle_connection(stream: TcpStream, addr: SocketAddr, server: Arc<Mute
     {
         let mut server = server.lock().unwrap();
         server.messages.insert(addr, tx);
-        server.lobby.insert(addr);
 
-        if server.lobby.len() == NUM_PLAYERS {
-            start_game(&mut server);
-        }
+        //let the user pick which variant to play
+        let variants = server.settings.iter().map(|settings| settings.variant).collect();
+        let message = SendMessage::Variants {variants};
+        server.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
     }
 
     let (outgoing, incoming) = ws_stream.split();
@@ -159,106 +193,572 @@ async fn handle_connection(stream: TcpStream, addr: SocketAddr, server: Arc<Mute
 
 }
 
-/// Starts a game given enough players have joined the lobby
-fn start_game(server: &mut Server) {
-    let mut persons = Vec::new();
+/// Puts a user in the lobby for a variant, starting a game once enough are waiting
+/// A user whose game is over leaves it first
+fn join_lobby(server: &mut Server, addr: SocketAddr, variant: Variant) {
+    if let Some(index) = server.game_map.get(&addr).copied() {
+        if !server.games[index].has_ended() {
+            println!("User {} tried to join the lobby from a game", addr);
+            return;
+        }
+        server.leave_game(addr, index);
+    }
+    server.stop_watching(addr);
+    let settings = match server.variant_settings(variant) {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    server.lobby.insert(addr, variant);
+    println!("User {} waiting for a {} game", addr, variant.name());
+
+    let waiting = server.lobby.values().filter(|waiting| **waiting == variant).count();
+    if waiting >= settings.players {
+        start_game(server, settings);
+    }
+}
 
-    let first = fastrand::usize(0..NUM_PLAYERS);
-    for (i, addr) in server.lobby.iter().enumerate() {
-        let messages = server.messages.get(&addr).unwrap();
+/// Starts a game given enough players have joined the lobby for its variant
+fn start_game(server: &mut Server, settings: GameSettings) {
+    let addrs = server.lobby.iter()
+        .filter(|(_, variant)| **variant == settings.variant)
+        .map(|(addr, _)| *addr)
+        .take(settings.players)
+        .collect::<Vec<_>>();
+
+    let first = fastrand::usize(0..addrs.len());
+    let persons = addrs.iter()
+        .enumerate()
+        .map(|(i, addr)| Player::new(Mark::from_index(i), *addr))
+        .collect::<Vec<_>>();
+
+    for addr in addrs.iter() {
+        server.lobby.remove(addr);
+    }
 
-        let (mark, first) = match i % 2 == 0 {
-            true => (Mark::Cross, i == first),
-            false => (Mark::Nought, i == first),
-        };
+    let game = settings.new_game(persons, first);
+    begin_game(server, settings, game);
+}
 
-        let player = Player::new(mark, *addr);
-        persons.push(player);
+/// Starts a rematch of a finished game once all its players want one
+fn start_rematch(server: &mut Server, index: usize) {
+    let variant = server.games[index].variant();
+    let settings = match server.variant_settings(variant) {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let game = settings.rematch(&server.games[index]);
+    begin_game(server, settings, game);
+    follow_game(server, index, server.games.len() - 1);
+}
 
-        let start_mess = SendMessage::StartGame {mark, first};
+/// Tells the players of a new game that it's starting and adds it to the server
+fn begin_game(server: &mut Server, settings: GameSettings, game: Game<SocketAddr, Box<dyn Rules>>) {
+    let first = game.get_curr_player().id();
+
+    //marks in the order players take turns
+    let order = game.get_turn_order().into_iter()
+        .map(|addr| game.get_player_mark(addr).unwrap())
+        .collect::<Vec<_>>();
+
+    for addr in game.get_player_ids() {
+        let start_mess = SendMessage::StartGame {
+            mark: game.get_player_mark(addr).unwrap(),
+            first: addr == first,
+            variant: settings.variant,
+            width: settings.width,
+            height: settings.height,
+            depth: settings.depth,
+            win_length: settings.win_length,
+            order: order.clone(),
+            role: game.get_player_role(addr),
+            clocks: game.clock_times(),
+        };
 
-        messages.unbounded_send(Message::Text(serde_json::to_string(&start_mess).unwrap())).unwrap();
+        server.send_one(addr, Message::Text(serde_json::to_string(&start_mess).unwrap()));
+        server.game_map.insert(addr, server.games.len());
     }
-    
 
-    let game = Game::new(persons, first);
+    server.games.push(game);
+    send_playable(server, server.games.len() - 1);
+}
 
-    for addr in server.lobby.iter() {
-        server.game_map.insert(*addr, server.games.len());
+/// Tells the players of a game which sub-boards can be played in, if the game has them
+fn send_playable(server: &Server, index: usize) {
+    if let Some(boards) = server.games[index].playable_boards() {
+        let message = SendMessage::Playable {boards};
+        server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
     }
-    server.lobby.clear();
+}
 
-    server.games.push(game);
+/// Sends the marks in a quantum game to its players, for other games nothing is sent
+fn send_quantum(server: &Server, index: usize) {
+    let game = &server.games[index];
+    if let Some(marks) = game.quantum_marks() {
+        let message = SendMessage::Quantum {
+            marks,
+            collapse: game.pending_collapse(),
+            next: game.get_curr_player().mark(),
+        };
+        server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+    }
 }
 
 /// Handles a message from a user
 fn handle_message(server: &mut Server, addr: SocketAddr, msg: Message) {
-    match msg {
-        Message::Text(message) => {
-            match serde_json::from_str::<ReceiveMessage>(&message) {
-                Ok(text) => {
-                    handle_receive(server, addr, text)
-                },
-                Err(_) => {},
-            }
-        },
-        _ => {},
+    if let Message::Text(message) = msg {
+        if let Ok(text) = serde_json::from_str::<ReceiveMessage>(&message) {
+            handle_receive(server, addr, text)
+        }
     }
 }
 
 /// Handles a receive message from a user
-/// Here to avoid excessive nesting but doesn't really do effective error handling
+/// Invalid moves are sent back to the user as an error
 fn handle_receive(server: &mut Server, addr: SocketAddr, msg: ReceiveMessage) {
+    //spectators can only watch
+    if msg.is_game_action() && server.watching.contains_key(&addr) {
+        server.send_rejected(addr, RequestError::Spectating);
+        return;
+    }
+
     match msg {
-        ReceiveMessage::Move {pos} => {
-            let game_index = server.game_map.get(&addr);
-
-            match game_index {
-                Some(index) => {
-                    let game = &mut server.games[*index];
-
-                    if game.can_move(&pos, addr) {
-                        let game_result = game.make_move(&pos);
-
-                        let mark = game.get_player_mark(addr).unwrap();
-                        let message = SendMessage::Move{mark: mark, pos: pos};
-
-                        let msg_str = serde_json::to_string(&message).unwrap();
-
-                        server.send_all_but_one(Message::Text(msg_str), addr, Some(*index));
-
-                        match game_result {
-                            Some(result) => {
-                                match result {
-                                    GameResult::CrossWon => {
-                                        for player_addr in server.games[*index].get_player_ids() {
-                                            let won = Mark::Cross == server.games[*index].get_player_mark(player_addr).unwrap();
-                                            let message = SendMessage::GameOver{winner: won, draw: false};
-                                            let msg = Message::Text(serde_json::to_string(&message).unwrap());
-                                            server.send_one(player_addr, msg);
-                                        }
-                                    },
-                                    GameResult::NoughtWon => {
-                                        for player_addr in server.games[*index].get_player_ids() {
-                                            let won = Mark::Nought == server.games[*index].get_player_mark(player_addr).unwrap();
-                                            let message = SendMessage::GameOver{winner: won, draw: false};
-                                            let msg = Message::Text(serde_json::to_string(&message).unwrap());
-                                            server.send_one(player_addr, msg);
-                                        }
-                                    },
-                                    GameResult::Draw => {
-                                        let message = SendMessage::GameOver{winner: false, draw: false};
-                                        let msg = Message::Text(serde_json::to_string(&message).unwrap());
-                                        server.send_all(msg, Some(*index));
-                                    },
-                                }
-                            },
-                            None => {},
-                        }
-                    }
-                },
-                None => {},
+        ReceiveMessage::JoinLobby {variant} => join_lobby(server, addr, variant),
+        ReceiveMessage::Move {pos, mark} => handle_move(server, addr, pos, mark),
+        ReceiveMessage::Drop {column} => {
+            let index = match move_game_index(server, addr) {
+                Some(index) => index,
+                None => return,
+            };
+            match server.games[index].column_square(column) {
+                Ok(pos) => handle_move(server, addr, pos, None),
+                Err(error) => server.send_error(addr, error),
+            }
+        },
+        ReceiveMessage::Collapse {pos} => handle_collapse(server, addr, pos),
+        ReceiveMessage::Resign => handle_resign(server, addr),
+        ReceiveMessage::OfferDraw => handle_draw_offer(server, addr, false),
+        ReceiveMessage::AcceptDraw => handle_draw_offer(server, addr, true),
+        ReceiveMessage::DeclineDraw => handle_decline_draw(server, addr),
+        ReceiveMessage::RequestRematch => handle_rematch(server, addr, false),
+        ReceiveMessage::AcceptRematch => handle_rematch(server, addr, true),
+        ReceiveMessage::OfferMatch {games} => handle_match_offer(server, addr, Some(games)),
+        ReceiveMessage::AcceptMatch => handle_match_offer(server, addr, None),
+        ReceiveMessage::ReturnToLobby => return_to_lobby(server, addr),
+        ReceiveMessage::ListGames => list_games(server, addr),
+        ReceiveMessage::Spectate {game} => spectate(server, addr, game),
+    }
+}
+
+/// Gets the index of the game a user is playing in, telling them if they aren't in one
+fn game_index(server: &Server, addr: SocketAddr) -> Option<usize> {
+    let index = server.game_map.get(&addr).copied();
+    if index.is_none() {
+        server.send_rejected(addr, RequestError::NotInGame);
+    }
+    index
+}
+
+/// Gets the index of the game a user is playing in, telling them with a move error if they aren't in one
+fn move_game_index(server: &Server, addr: SocketAddr) -> Option<usize> {
+    let index = server.game_map.get(&addr).copied();
+    if index.is_none() {
+        server.send_error(addr, MoveError::NotInGame);
+    }
+    index
+}
+
+/// Handles a move from a user, sending an error back if the move can't be made
+/// The mark placed is the player's own unless they chose one
+fn handle_move(server: &mut Server, addr: SocketAddr, pos: Square, mark: Option<Mark>) {
+    let index = match move_game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        server.send_error(addr, MoveError::GameOver);
+        return;
+    }
+
+    let game = &server.games[index];
+
+    let piece = mark.unwrap_or_else(|| game.get_curr_player().mark());
+    if let Err(error) = game.can_move_with(&pos, addr, piece) {
+        server.send_error(addr, error);
+        return;
+    }
+
+    play_move(server, index, addr, pos, piece);
+}
+
+/// Handles a user picking where a quantum move collapses, sending an error back if it can't
+fn handle_collapse(server: &mut Server, addr: SocketAddr, pos: Square) {
+    let index = match move_game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        server.send_error(addr, MoveError::GameOver);
+        return;
+    }
+
+    let game = &server.games[index];
+
+    if let Err(error) = game.can_collapse(&pos, addr) {
+        server.send_error(addr, error);
+        return;
+    }
+
+    let piece = game.get_curr_player().mark();
+    play_move(server, index, addr, pos, piece);
+}
+
+/// Handles a user resigning from their game
+fn handle_resign(server: &mut Server, addr: SocketAddr) {
+    let index = match game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        server.send_rejected(addr, RequestError::GameOver);
+        return;
+    }
+
+    match server.games[index].resign(addr) {
+        Ok(result) => send_game_over(server, index, result),
+        Err(error) => server.send_rejected(addr, error),
+    }
+}
+
+/// Handles a user offering a draw or accepting someone else's offer
+/// The game is drawn once everyone agrees, until then everyone is told who has
+fn handle_draw_offer(server: &mut Server, addr: SocketAddr, accept: bool) {
+    let index = match game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        server.send_rejected(addr, RequestError::GameOver);
+        return;
+    }
+
+    let game = &mut server.games[index];
+    let agreed = if accept {game.accept_draw(addr)} else {game.offer_draw(addr)};
+    match agreed {
+        Ok(Some(result)) => send_game_over(server, index, result),
+        Ok(None) => {
+            let message = SendMessage::DrawOffer {marks: server.games[index].draw_offer()};
+            server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+        },
+        Err(error) => server.send_rejected(addr, error),
+    }
+}
+
+/// Handles a user turning down a draw offer, telling everyone in the game
+fn handle_decline_draw(server: &mut Server, addr: SocketAddr) {
+    let index = match game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        server.send_rejected(addr, RequestError::GameOver);
+        return;
+    }
+
+    let game = &mut server.games[index];
+    match game.decline_draw(addr) {
+        Ok(()) => {
+            let message = SendMessage::DrawDeclined {mark: game.get_player_mark(addr).unwrap()};
+            server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+        },
+        Err(error) => server.send_rejected(addr, error),
+    }
+}
+
+/// Handles a user asking for a rematch or agreeing to one, starting it once everyone wants one
+fn handle_rematch(server: &mut Server, addr: SocketAddr, accept: bool) {
+    let index = match game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    //the flag falling is news enough, and in a match the players may have moved on to the next game
+    if check_flag(server, index) {
+        return;
+    }
+
+    let game = &mut server.games[index];
+    let agreed = if accept {game.accept_rematch(addr)} else {game.request_rematch(addr)};
+    match agreed {
+        Ok(true) => start_rematch(server, index),
+        Ok(false) => {
+            let message = SendMessage::RematchOffer {marks: server.games[index].rematch_offer()};
+            server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+        },
+        Err(error) => server.send_rejected(addr, error),
+    }
+}
+
+/// Handles a user offering a match of some number of games, or agreeing to one when the number is None
+/// The match starts once everyone agrees
+fn handle_match_offer(server: &mut Server, addr: SocketAddr, games: Option<usize>) {
+    let index = match game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        return;
+    }
+
+    let game = &mut server.games[index];
+    let agreed = match games {
+        Some(games) => game.offer_match(addr, games),
+        None => game.accept_match(addr),
+    };
+    match agreed {
+        Ok(Some(games)) => start_match(server, index, games),
+        Ok(None) => {
+            let (games, marks) = server.games[index].match_offer().unwrap();
+            let message = SendMessage::MatchOffer {games, marks};
+            server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+        },
+        Err(error) => server.send_rejected(addr, error),
+    }
+}
+
+/// Starts a match between the players of a finished game, its first game being a rematch
+fn start_match(server: &mut Server, index: usize, games: usize) {
+    let variant = server.games[index].variant();
+    let settings = match server.variant_settings(variant) {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let marks = server.games[index].get_player_ids().into_iter()
+        .map(|addr| server.games[index].get_player_mark(addr).unwrap())
+        .collect();
+    let series = Match::new(settings, games, marks);
+    if let Some(game) = series.next_game(&server.games[index]) {
+        begin_game(server, settings, game);
+        let next = server.games.len() - 1;
+        follow_game(server, index, next);
+        send_match_score(server, next, &series);
+        server.matches.insert(next, series);
+    }
+}
+
+/// Adds the result of a game to its match, if it's part of one, then starts the next game or ends the match
+fn continue_match(server: &mut Server, index: usize, result: &GameResult) {
+    let mut series = match server.matches.remove(&index) {
+        Some(series) => series,
+        None => return,
+    };
+    series.record(result);
+    send_match_score(server, index, &series);
+
+    match series.next_game(&server.games[index]) {
+        Some(game) => {
+            begin_game(server, series.settings(), game);
+            let next = server.games.len() - 1;
+            follow_game(server, index, next);
+            server.matches.insert(next, series);
+        },
+        None => {
+            let winner = series.winner();
+            for player_addr in server.games[index].get_player_ids() {
+                let won = winner.is_some() && winner == server.games[index].get_player_mark(player_addr);
+                let message = SendMessage::MatchOver {winner: won, draw: winner.is_none()};
+                server.send_one(player_addr, Message::Text(serde_json::to_string(&message).unwrap()));
             }
+        },
+    }
+}
+
+/// Tells the players of a game in a match what the score is
+fn send_match_score(server: &Server, index: usize, series: &Match) {
+    let message = SendMessage::MatchScore {
+        games: series.length(),
+        played: series.played(),
+        scores: series.scores().to_vec(),
+        draws: series.draws(),
+    };
+    server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+}
+
+/// Sends a user the games going on that they can watch
+fn list_games(server: &Server, addr: SocketAddr) {
+    let games = server.games.iter()
+        .enumerate()
+        .filter(|(_, game)| !game.has_ended())
+        .map(|(index, game)| LiveGame::new(index, game.variant()))
+        .collect();
+    let message = SendMessage::Games {games};
+    server.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
+}
+
+/// Lets a user watch a game going on, sending them the game as it is now
+/// A user whose game is over leaves it first, and anyone waiting in the lobby stops waiting
+fn spectate(server: &mut Server, addr: SocketAddr, index: usize) {
+    if let Some(playing) = server.game_map.get(&addr).copied() {
+        if !server.games[playing].has_ended() {
+            server.send_rejected(addr, RequestError::InGame);
+            return;
+        }
+        server.leave_game(addr, playing);
+    }
+    if index >= server.games.len() || server.games[index].has_ended() {
+        server.send_rejected(addr, RequestError::NoSuchGame);
+        return;
+    }
+
+    server.lobby.remove(&addr);
+    server.stop_watching(addr);
+    server.spectators.entry(index).or_default().push(addr);
+    server.watching.insert(addr, index);
+    println!("User {} watching game {}", addr, index);
+    send_spectating(server, addr, index);
+}
+
+/// Moves everyone watching a game on to the game after it, like the next game of a match
+fn follow_game(server: &mut Server, index: usize, next: usize) {
+    let spectators = server.spectators.remove(&index).unwrap_or_default();
+    for addr in spectators.iter() {
+        server.watching.insert(*addr, next);
+        send_spectating(server, *addr, next);
+    }
+    server.spectators.insert(next, spectators);
+}
+
+/// Sends a spectator the game they're watching as it is now
+fn send_spectating(server: &Server, addr: SocketAddr, index: usize) {
+    let game = &server.games[index];
+    let board = game.board();
+    let message = SendMessage::Spectating {
+        game: index,
+        variant: game.variant(),
+        width: board.width(),
+        height: board.height(),
+        depth: board.depth(),
+        win_length: board.win_length(),
+        order: game.get_turn_order().into_iter().map(|addr| game.get_player_mark(addr).unwrap()).collect(),
+        board: board.marks(),
+        next: game.get_curr_player().mark(),
+        clocks: game.clock_times(),
+    };
+    server.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
+
+    //some variants need more than the board to show the game
+    if let Some(marks) = game.quantum_marks() {
+        let message = SendMessage::Quantum {
+            marks,
+            collapse: game.pending_collapse(),
+            next: game.get_curr_player().mark(),
+        };
+        server.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
+    }
+    if let Some(boards) = game.playable_boards() {
+        let message = SendMessage::Playable {boards};
+        server.send_one(addr, Message::Text(serde_json::to_string(&message).unwrap()));
+    }
+}
+
+/// Takes a user out of their finished game and puts them back in the lobby for the same variant
+fn return_to_lobby(server: &mut Server, addr: SocketAddr) {
+    let index = match game_index(server, addr) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, index) {
+        return;
+    }
+
+    if !server.games[index].has_ended() {
+        server.send_rejected(addr, RequestError::GameNotOver);
+        return;
+    }
+    let variant = server.games[index].variant();
+    join_lobby(server, addr, variant);
+}
+
+/// Makes a move that's already been checked, then tells the players in the game about it
+fn play_move(server: &mut Server, index: usize, addr: SocketAddr, pos: Square, piece: Mark) {
+    let game = &mut server.games[index];
+    let offered = !game.draw_offer().is_empty();
+    let game_result = game.make_move_with(&pos, piece);
+    //the rules decide what actually went on the board, like a cross for everyone in Notakto
+    let piece = game.history().last().unwrap().piece();
+
+    let mark = game.get_player_mark(addr).unwrap();
+    let message = SendMessage::Move{mark, pos, piece, clocks: game.clock_times()};
+
+    let msg_str = serde_json::to_string(&message).unwrap();
+
+    //everyone gets the move, including who made it so they know where it ended up
+    server.send_all(Message::Text(msg_str), Some(index));
+    send_quantum(server, index);
+
+    //moving turns down any draw offer the mover hadn't agreed to
+    if offered && game_result.is_none() && server.games[index].draw_offer().is_empty() {
+        let message = SendMessage::DrawDeclined {mark};
+        server.send_all(Message::Text(serde_json::to_string(&message).unwrap()), Some(index));
+    }
+
+    match game_result {
+        Some(result) => send_game_over(server, index, result),
+        None => send_playable(server, index),
+    }
+}
+
+/// Ends the game if the player to move has run out of time, telling the players
+/// Returns whether their time ran out
+fn check_flag(server: &mut Server, index: usize) -> bool {
+    match server.games[index].check_flag() {
+        Some(result) => {
+            send_game_over(server, index, result);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Checks every game's clocks, so players who stop moving still run out of time
+fn check_flags(server: &mut Server) {
+    for index in 0..server.games.len() {
+        check_flag(server, index);
+    }
+}
+
+/// Tells the players of a game how it ended, then carries on with its match if it's part of one
+fn send_game_over(server: &mut Server, index: usize, result: GameResult) {
+    let line = result.line().cloned();
+    let line_lost = result.is_line_lost();
+    let reason = result.reason();
+    let marks = server.games[index].get_player_ids().into_iter()
+        .map(|player_addr| server.games[index].get_player_mark(player_addr).unwrap());
+    let winners = marks.filter(|mark| result.won_by(*mark)).collect::<Vec<_>>();
+    if result.is_draw() {
+        let message = SendMessage::GameOver{winner: false, draw: true, line, line_lost, reason, winners};
+        let msg = Message::Text(serde_json::to_string(&message).unwrap());
+        server.send_all(msg, Some(index));
+    } else {
+        for player_addr in server.games[index].get_player_ids() {
+            let won = result.won_by(server.games[index].get_player_mark(player_addr).unwrap());
+            let message = SendMessage::GameOver{winner: won, draw: false, line: line.clone(), line_lost, reason, winners: winners.clone()};
+            let msg = Message::Text(serde_json::to_string(&message).unwrap());
+            server.send_one(player_addr, msg);
+        }
+        let message = SendMessage::GameOver{winner: false, draw: false, line, line_lost, reason, winners};
+        for spectator in server.game_spectators(index) {
+            server.send_one(spectator, Message::Text(serde_json::to_string(&message).unwrap()));
         }
     }
-}
\ No newline at end of file
+    continue_match(server, index, &result);
+}
@@ -0,0 +1,91 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use common::game::{Board, Game, Mark, Player, Square};
+use common::solver::solve;
+
+/// Fills a board with marks in a pattern that doesn't give anyone a line
+fn drawn_board(size: usize, win_length: usize) -> Board {
+    let mut board = Board::new(size, size, win_length);
+    for y in 0..size {
+        for x in 0..size {
+            let mark = if (x + 2 * y) % 4 < 2 {Mark::Cross} else {Mark::Nought};
+            board.set_pos(x, y, mark);
+        }
+    }
+    board
+}
+
+/// Checks for a line by rescanning every row, column and diagonal of the marks, like the old array board did
+/// This is the baseline the bitboard checks are compared against
+fn rescan_game_over(marks: &[Mark], size: usize, win_length: usize) -> Option<Mark> {
+    let directions = [(1, 0), (0, 1), (1, 1), (1, -1)];
+    for y in 0..size as isize {
+        for x in 0..size as isize {
+            let mark = marks[(y * size as isize + x) as usize];
+            if mark == Mark::Empty {
+                continue;
+            }
+            for (dx, dy) in directions.iter() {
+                let in_line = (1..win_length as isize).all(|i| {
+                    let (lx, ly) = (x + dx * i, y + dy * i);
+                    lx >= 0 && ly >= 0 && lx < size as isize && ly < size as isize
+                        && marks[(ly * size as isize + lx) as usize] == mark
+                });
+                if in_line {
+                    return Some(mark);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Create a new game between two players
+fn new_game(size: usize, win_length: usize) -> Game<usize> {
+    let players = vec![Player::new(Mark::Cross, 0), Player::new(Mark::Nought, 1)];
+    Game::new(players, 0, size, size, win_length)
+}
+
+/// Plays a whole game with random moves using the game's own move checking
+fn random_game(mut game: Game<usize>, rng: &fastrand::Rng) {
+    let mut squares = game.board().empty_squares();
+    while !squares.is_empty() {
+        let square = squares.swap_remove(rng.usize(0..squares.len()));
+        if game.make_move(&square).is_some() {
+            break;
+        }
+    }
+}
+
+fn game_over(c: &mut Criterion) {
+    for (size, win_length) in [(3, 3), (15, 5)].iter() {
+        let board = drawn_board(*size, *win_length);
+        let marks = board.marks();
+        let last = Square::new(size / 2, size / 2);
+        let mut group = c.benchmark_group(format!("game_over {}x{}", size, size));
+        group.bench_function("array rescan", |b| {
+            b.iter(|| rescan_game_over(black_box(&marks), *size, *win_length))
+        });
+        group.bench_function("bitboard", |b| b.iter(|| black_box(&board).game_over()));
+        group.bench_function("bitboard last move", |b| b.iter(|| black_box(&board).game_over_at_square(&last)));
+        group.finish();
+    }
+}
+
+fn self_play(c: &mut Criterion) {
+    let rng = fastrand::Rng::with_seed(0);
+    c.bench_function("random game 3x3", |b| {
+        b.iter_batched(|| new_game(3, 3), |game| random_game(game, &rng), BatchSize::SmallInput)
+    });
+    c.bench_function("random game 15x15", |b| {
+        b.iter_batched(|| new_game(15, 5), |game| random_game(game, &rng), BatchSize::SmallInput)
+    });
+}
+
+fn solver(c: &mut Criterion) {
+    let board = Board::new(3, 3, 3);
+    c.bench_function("solve 3x3", |b| b.iter(|| solve(black_box(&board), Mark::Cross)));
+}
+
+criterion_group!(benches, game_over, self_play, solver);
+criterion_main!(benches);
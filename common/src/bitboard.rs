@@ -0,0 +1,150 @@
+/// Bitboards used to store a board, one bit per square
+/// Boards can be any size so the bits are split up into 64 bit words
+use std::sync::{Arc, Mutex};
+
+const WORD_BITS: usize = 64;
+
+/// Width, height, depth and win length of a board
+type BoardSize = (usize, usize, usize, usize);
+
+/// Lines already worked out for each board size
+static LINE_CACHE: Mutex<Vec<(BoardSize, Arc<WinLines>)>> = Mutex::new(Vec::new());
+
+/// A set of squares on a board, bit (z * height + y) * width + x is the square at x, y, z
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Bitboard {
+    words: Vec<u64>,
+}
+
+impl Bitboard {
+    /// Create an empty bitboard that can hold a number of squares
+    pub(crate) fn new(size: usize) -> Self {
+        Self {
+            words: vec![0; size.div_ceil(WORD_BITS)],
+        }
+    }
+
+    /// Gets whether a square is in the set
+    pub(crate) fn get(&self, spot: usize) -> bool {
+        self.words[spot / WORD_BITS] & (1 << (spot % WORD_BITS)) != 0
+    }
+
+    /// Adds a square to the set
+    pub(crate) fn set(&mut self, spot: usize) {
+        self.words[spot / WORD_BITS] |= 1 << (spot % WORD_BITS);
+    }
+
+    /// Removes a square from the set
+    pub(crate) fn clear(&mut self, spot: usize) {
+        self.words[spot / WORD_BITS] &= !(1 << (spot % WORD_BITS));
+    }
+
+    /// Gets whether every square of a line is in the set
+    pub(crate) fn contains_line(&self, line: &Line) -> bool {
+        line.mask.iter().all(|(word, mask)| self.words[*word] & mask == *mask)
+    }
+}
+
+/// A line of squares that wins the game if they all have the same mark
+/// Only the words that the line touches are stored
+#[derive(Debug)]
+pub(crate) struct Line {
+    spots: Vec<usize>,
+    mask: Vec<(usize, u64)>,
+}
+
+impl Line {
+    /// Create a line from the squares in it
+    fn new(spots: &[usize]) -> Self {
+        let mut mask: Vec<(usize, u64)> = Vec::new();
+        for spot in spots {
+            let word = spot / WORD_BITS;
+            let bit = 1 << (spot % WORD_BITS);
+            match mask.iter_mut().find(|(index, _)| *index == word) {
+                Some((_, bits)) => *bits |= bit,
+                None => mask.push((word, bit)),
+            }
+        }
+        Self {
+            spots: spots.to_vec(),
+            mask,
+        }
+    }
+
+    /// Gets the squares in the line as single coords
+    pub(crate) fn spots(&self) -> &[usize] {
+        &self.spots
+    }
+}
+
+/// Every winning line on a board, along with which lines go through each square
+/// These only depend on the dimensions so are shared between copies of a board
+#[derive(Debug)]
+pub(crate) struct WinLines {
+    lines: Vec<Line>,
+    through: Vec<Vec<usize>>,
+}
+
+impl WinLines {
+    /// Gets all the winning lines for a board size, reusing them if they've already been worked out
+    pub(crate) fn new(width: usize, height: usize, depth: usize, win_length: usize) -> Arc<Self> {
+        let key = (width, height, depth, win_length);
+        let mut cache = LINE_CACHE.lock().unwrap();
+        if let Some((_, lines)) = cache.iter().find(|(size, _)| *size == key) {
+            return lines.clone();
+        }
+
+        let lines = Self::generate(width, height, depth, win_length);
+        cache.push((key, lines.clone()));
+        lines
+    }
+
+    /// Work out all the winning lines for a board size
+    fn generate(width: usize, height: usize, depth: usize, win_length: usize) -> Arc<Self> {
+        //directions to make lines in, only going forwards so each line is only made once
+        //flat boards only use the first four, the rest go between layers
+        const DIRECTIONS: [(isize, isize, isize); 13] = [
+            (1, 0, 0), (0, 1, 0), (1, 1, 0), (-1, 1, 0),
+            (0, 0, 1), (1, 0, 1), (-1, 0, 1), (0, 1, 1), (0, -1, 1),
+            (1, 1, 1), (-1, 1, 1), (1, -1, 1), (-1, -1, 1),
+        ];
+
+        let mut lines = Vec::new();
+        let mut through = vec![Vec::new(); width * height * depth];
+        let size = [width as isize, height as isize, depth as isize];
+        let inside = |coord: [isize; 3]| (0..3).all(|i| coord[i] >= 0 && coord[i] < size[i]);
+
+        for z in 0..depth as isize {
+            for y in 0..height as isize {
+                for x in 0..width as isize {
+                    for (dx, dy, dz) in DIRECTIONS.iter() {
+                        let steps = win_length as isize - 1;
+                        if !inside([x + dx * steps, y + dy * steps, z + dz * steps]) {
+                            continue;
+                        }
+
+                        let spots = (0..win_length as isize)
+                            .map(|i| (((z + dz * i) * height as isize + y + dy * i) * width as isize + x + dx * i) as usize)
+                            .collect::<Vec<_>>();
+                        for spot in spots.iter() {
+                            through[*spot].push(lines.len());
+                        }
+                        lines.push(Line::new(&spots));
+                    }
+                }
+            }
+        }
+
+        Arc::new(Self { lines, through })
+    }
+
+    /// Gets all the lines on the board
+    pub(crate) fn all(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Gets the lines that go through a square
+    pub(crate) fn through(&self, spot: usize) -> impl Iterator<Item = &Line> {
+        self.through[spot].iter().map(move |index| &self.lines[*index])
+    }
+}
@@ -0,0 +1,189 @@
+/// Chess style clocks, each player has some time for the whole game and their clock only runs on their turn
+/// A time control is written like 300+2 for 300 seconds each with 2 seconds added after every move (Fischer),
+/// or 300d2 for 300 seconds each with up to 2 seconds of each move given back (Bronstein)
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::game::Mark;
+
+/// How time is given back to a player after they move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Increment {
+    //a fixed amount is added after every move
+    Fischer(Duration),
+    //the time the move took is given back, up to a fixed amount
+    Bronstein(Duration),
+}
+
+/// How much time players get for a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    base: Duration,
+    increment: Increment,
+}
+
+impl TimeControl {
+    /// Create a time control where each player starts with some time
+    pub fn new(base: Duration, increment: Increment) -> Self {
+        Self {base, increment}
+    }
+
+    /// Reads a time control written like 300+2 or 300d2, in seconds
+    pub fn parse(text: &str) -> Option<Self> {
+        let (split, kind): (usize, fn(Duration) -> Increment) = match (text.find('+'), text.find('d')) {
+            (Some(split), None) => (split, Increment::Fischer),
+            (None, Some(split)) => (split, Increment::Bronstein),
+            (None, None) => (text.len(), Increment::Fischer),
+            _ => return None,
+        };
+        let base = text[..split].parse::<u64>().ok().filter(|base| *base > 0)?;
+        let increment = match text.get(split + 1..) {
+            Some(increment) => increment.parse::<u64>().ok()?,
+            None => 0,
+        };
+        Some(Self::new(Duration::from_secs(base), kind(Duration::from_secs(increment))))
+    }
+
+    /// Gets the time each player starts with
+    pub fn base(&self) -> Duration {
+        self.base
+    }
+
+    /// Gets how time is given back after each move
+    pub fn increment(&self) -> Increment {
+        self.increment
+    }
+
+    /// Gets how much time is given back after a move that took some time
+    fn bonus(&self, taken: Duration) -> Duration {
+        match self.increment {
+            Increment::Fischer(added) => added,
+            Increment::Bronstein(delay) => taken.min(delay),
+        }
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.increment {
+            Increment::Fischer(added) => write!(f, "{}+{}", self.base.as_secs(), added.as_secs()),
+            Increment::Bronstein(delay) => write!(f, "{}d{}", self.base.as_secs(), delay.as_secs()),
+        }
+    }
+}
+
+/// The time a player has left, sent to players so they can show the clocks
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ClockTime {
+    mark: Mark,
+    //milliseconds left
+    remaining: u64,
+    running: bool,
+}
+
+impl ClockTime {
+    /// Gets the mark of the player the clock is for
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Gets how long the player has left
+    pub fn remaining(&self) -> Duration {
+        Duration::from_millis(self.remaining)
+    }
+
+    /// Gets whether the clock is counting down, which it does on the player's turn
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+}
+
+/// Clocks for every player in a game, by the index of the player
+#[derive(Debug, Clone)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: Vec<Duration>,
+    //the player whose clock is running and when their turn started
+    running: Option<(usize, Instant)>,
+}
+
+impl Clock {
+    /// Create clocks for some players, with nobody's running yet
+    pub fn new(control: TimeControl, players: usize) -> Self {
+        Self {
+            control,
+            remaining: vec![control.base; players],
+            running: None,
+        }
+    }
+
+    /// Gets the time control the clocks were set with
+    pub fn control(&self) -> TimeControl {
+        self.control
+    }
+
+    /// Gets the player whose clock is running
+    pub fn running(&self) -> Option<usize> {
+        self.running.map(|(player, _)| player)
+    }
+
+    /// Gets how long a player has left at some time
+    pub fn remaining(&self, player: usize, now: Instant) -> Duration {
+        match self.running {
+            Some((running, since)) if running == player => {
+                self.remaining[player].saturating_sub(now.saturating_duration_since(since))
+            },
+            _ => self.remaining[player],
+        }
+    }
+
+    /// Gets whether the running clock had no time left at some time
+    pub fn flag_fallen(&self, now: Instant) -> bool {
+        match self.running {
+            Some((player, _)) => self.remaining(player, now) == Duration::from_secs(0),
+            None => false,
+        }
+    }
+
+    /// Stops the running clock and starts the clock of the player to move next
+    /// The player who moved gets time back for their move, unless they're also moving next
+    pub fn switch(&mut self, next: usize, now: Instant) {
+        if self.running() != Some(next) {
+            self.stop(now);
+            self.running = Some((next, now));
+        }
+    }
+
+    /// Stops the running clock, giving the player time back for their move
+    pub fn stop(&mut self, now: Instant) {
+        if let Some((player, since)) = self.running.take() {
+            let taken = now.saturating_duration_since(since);
+            let left = self.remaining[player].saturating_sub(taken);
+            self.remaining[player] = if left > Duration::from_secs(0) {left + self.control.bonus(taken)} else {left};
+        }
+    }
+
+    /// Stops the running clock without giving time back and starts the clock of some player
+    /// Moves being taken back or played again don't earn any time
+    pub fn hand_to(&mut self, player: usize, now: Instant) {
+        if let Some((running, since)) = self.running.take() {
+            self.remaining[running] = self.remaining[running].saturating_sub(now.saturating_duration_since(since));
+        }
+        self.running = Some((player, now));
+    }
+
+    /// Gets the time every player has left at some time, with their marks given by the index of the player
+    pub fn times(&self, marks: &[Mark], now: Instant) -> Vec<ClockTime> {
+        marks.iter()
+            .enumerate()
+            .map(|(player, mark)| ClockTime {
+                mark: *mark,
+                remaining: self.remaining(player, now).as_millis() as u64,
+                running: self.running() == Some(player),
+            })
+            .collect()
+    }
+}
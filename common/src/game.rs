@@ -1,61 +1,775 @@
-const BOARD_SIZE: usize = 3;
+/// Width, height and win length of a standard game
+pub const DEFAULT_BOARD_SIZE: usize = 3;
+/// Number of players in a standard game
 pub const NUM_PLAYERS: usize = 2;
+/// Most players a game can have, marks past noughts are written as the digits 2 to 9
+pub const MAX_PLAYERS: usize = 10;
+/// Start of the command line argument that gives games clocks
+pub const TIME_ARG: &str = "time=";
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
 
 use serde::{Deserialize, Serialize};
 
-/// Represents a tictactoe game
-pub struct Game<T> {
+use crate::bitboard::{Bitboard, Line, WinLines};
+use crate::clock::{Clock, ClockTime, TimeControl};
+use crate::gomoku::{self, Gomoku, GomokuRule};
+use crate::gravity::{self, Gravity};
+use crate::misere::{self, Misere, Notakto};
+use crate::order::{self, OrderChaos};
+use crate::quantum::{Quantum, QuantumMark};
+use crate::qubic::{self, Qubic};
+use crate::rules::{Rules, Standard};
+use crate::series::MATCH_LENGTHS;
+use crate::ultimate::{self, Ultimate};
+use crate::wild::Wild;
+
+/// The different kinds of game that can be played
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum Variant {
+    Standard,
+    Ultimate,
+    Gravity,
+    Misere,
+    Notakto,
+    Qubic,
+    //standard Gomoku, where only exactly five in a row wins
+    Gomoku,
+    Freestyle,
+    Renju,
+    //either player can place either mark
+    Wild,
+    Quantum,
+    OrderChaos,
+}
+
+impl Variant {
+    /// Every variant, in the order they're listed to players
+    pub const ALL: [Variant; 12] = [
+        Variant::Standard,
+        Variant::Ultimate,
+        Variant::Gravity,
+        Variant::Misere,
+        Variant::Notakto,
+        Variant::Qubic,
+        Variant::Gomoku,
+        Variant::Freestyle,
+        Variant::Renju,
+        Variant::Wild,
+        Variant::Quantum,
+        Variant::OrderChaos,
+    ];
+
+    /// Gets the variant with a name, ignoring case
+    pub fn from_name(name: &str) -> Option<Variant> {
+        match name.to_ascii_lowercase().as_str() {
+            "standard" => Some(Variant::Standard),
+            "ultimate" => Some(Variant::Ultimate),
+            "gravity" => Some(Variant::Gravity),
+            "misere" => Some(Variant::Misere),
+            "notakto" => Some(Variant::Notakto),
+            "qubic" => Some(Variant::Qubic),
+            "gomoku" => Some(Variant::Gomoku),
+            "freestyle" => Some(Variant::Freestyle),
+            "renju" => Some(Variant::Renju),
+            "wild" => Some(Variant::Wild),
+            "quantum" => Some(Variant::Quantum),
+            "orderchaos" => Some(Variant::OrderChaos),
+            _ => None,
+        }
+    }
+
+    /// Gets the name of the variant
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::Ultimate => "Ultimate",
+            Variant::Gravity => "Gravity",
+            Variant::Misere => "Misere",
+            Variant::Notakto => "Notakto",
+            Variant::Qubic => "Qubic",
+            Variant::Gomoku => "Gomoku",
+            Variant::Freestyle => "Freestyle",
+            Variant::Renju => "Renju",
+            Variant::Wild => "Wild",
+            Variant::Quantum => "Quantum",
+            Variant::OrderChaos => "OrderChaos",
+        }
+    }
+}
+
+/// Settings for the games a server starts
+#[derive(Debug, Clone, Copy)]
+pub struct GameSettings {
+    pub variant: Variant,
+    pub players: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Number of layers, 1 for a flat board
+    pub depth: usize,
+    pub win_length: usize,
+    /// How much time players get, None for games without clocks
+    pub time_control: Option<TimeControl>,
+}
+
+impl GameSettings {
+    /// Reads settings from command line arguments in the order players, width, height, win length
+    /// The first argument can be the name of a variant, otherwise the settings are for the standard game
+    /// Any arguments left out use the variant's usual settings, some variants like ultimate always have the same settings
+    /// Notakto only takes the number of players then the number of boards
+    /// Clocks are added with an argument like time=300+2 anywhere in the arguments, see TimeControl::parse
+    pub fn from_args<I: Iterator<Item = String>>(args: I) -> Result<Self, String> {
+        let (times, args): (Vec<_>, Vec<_>) = args.partition(|arg| arg.starts_with(TIME_ARG));
+        let mut settings = Self::board_from_args(args.into_iter())?;
+        for time in times {
+            let control = TimeControl::parse(&time[TIME_ARG.len()..])
+                .ok_or_else(|| format!("Time control {} should be seconds each plus an increment, like time=300+2 or time=300d2", time))?;
+            settings.time_control = Some(control);
+        }
+        Ok(settings)
+    }
+
+    /// Reads the variant and board settings from the arguments that aren't a time control
+    fn board_from_args<I: Iterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut args = args.peekable();
+        let variant = match args.peek().and_then(|arg| Variant::from_name(arg)) {
+            Some(variant) => {
+                args.next();
+                variant
+            },
+            None => Variant::Standard,
+        };
+        if let Some(fixed) = fixed_settings(variant) {
+            if let Some(arg) = args.next() {
+                return Err(format!("Unexpected argument {}, {}", arg, fixed));
+            }
+            return Ok(Self::for_variant(variant));
+        }
+        if variant == Variant::Notakto {
+            return Self::notakto_from_args(args);
+        }
+
+        let usual = Self::for_variant(variant);
+        let mut values = [usual.players, usual.width, usual.height, usual.win_length];
+        for (i, arg) in args.enumerate() {
+            if i >= values.len() {
+                return Err(format!("Unexpected argument {}", arg));
+            }
+            values[i] = arg.parse().map_err(|_| format!("Argument {} should be a number", arg))?;
+        }
+
+        let [players, width, height, win_length] = values;
+        Self::check_players(players)?;
+        if width == 0 || height == 0 || win_length == 0 || win_length > width.max(height) {
+            return Err("The win length has to fit on the board".to_string());
+        }
+        Ok(Self {variant, players, width, height, depth: 1, win_length, time_control: None})
+    }
+
+    /// Checks a game has a number of players marks can be given to, which is between 2 and MAX_PLAYERS
+    pub fn check_players(players: usize) -> Result<(), String> {
+        if (NUM_PLAYERS..=MAX_PLAYERS).contains(&players) {
+            Ok(())
+        } else {
+            Err(format!("There have to be between {} and {} players", NUM_PLAYERS, MAX_PLAYERS))
+        }
+    }
+
+    /// Reads the number of players and boards for Notakto
+    fn notakto_from_args<I: Iterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut values = [NUM_PLAYERS, misere::NOTAKTO_BOARDS];
+        for (i, arg) in args.enumerate() {
+            if i >= values.len() {
+                return Err(format!("Unexpected argument {}, Notakto only takes players and boards", arg));
+            }
+            values[i] = arg.parse().map_err(|_| format!("Argument {} should be a number", arg))?;
+        }
+
+        let [players, boards] = values;
+        Self::check_players(players)?;
+        if boards == 0 {
+            return Err("There has to be at least one board".to_string());
+        }
+        Ok(Self {
+            variant: Variant::Notakto,
+            players,
+            width: boards * ultimate::SUB_SIZE,
+            height: ultimate::SUB_SIZE,
+            depth: 1,
+            win_length: ultimate::SUB_SIZE,
+            time_control: None,
+        })
+    }
+
+    /// Gets the usual settings for a variant
+    pub fn for_variant(variant: Variant) -> Self {
+        match variant {
+            Variant::Standard => Self::default(),
+            Variant::Ultimate => Self {
+                variant,
+                players: NUM_PLAYERS,
+                width: ultimate::SIZE,
+                height: ultimate::SIZE,
+                depth: 1,
+                win_length: ultimate::SUB_SIZE,
+                time_control: None,
+            },
+            Variant::Gravity => Self {
+                variant,
+                players: NUM_PLAYERS,
+                width: gravity::WIDTH,
+                height: gravity::HEIGHT,
+                depth: 1,
+                win_length: gravity::WIN_LENGTH,
+                time_control: None,
+            },
+            Variant::Misere | Variant::Wild | Variant::Quantum => Self {variant, ..Self::default()},
+            Variant::Notakto => Self {
+                variant,
+                players: NUM_PLAYERS,
+                width: misere::NOTAKTO_BOARDS * ultimate::SUB_SIZE,
+                height: ultimate::SUB_SIZE,
+                depth: 1,
+                win_length: ultimate::SUB_SIZE,
+                time_control: None,
+            },
+            Variant::Qubic => Self {
+                variant,
+                players: NUM_PLAYERS,
+                width: qubic::SIZE,
+                height: qubic::SIZE,
+                depth: qubic::SIZE,
+                win_length: qubic::SIZE,
+                time_control: None,
+            },
+            Variant::Gomoku | Variant::Freestyle | Variant::Renju => Self {
+                variant,
+                players: NUM_PLAYERS,
+                width: gomoku::SIZE,
+                height: gomoku::SIZE,
+                depth: 1,
+                win_length: gomoku::WIN_LENGTH,
+                time_control: None,
+            },
+            Variant::OrderChaos => Self {
+                variant,
+                players: NUM_PLAYERS,
+                width: order::SIZE,
+                height: order::SIZE,
+                depth: 1,
+                win_length: order::WIN_LENGTH,
+                time_control: None,
+            },
+        }
+    }
+
+    /// Gets settings for every variant a server hosts, using these settings for their own variant
+    /// Every variant gets the same time control
+    pub fn hosted(self) -> Vec<Self> {
+        Variant::ALL.iter()
+            .map(|variant| if *variant == self.variant { self } else { Self::for_variant(*variant) })
+            .map(|settings| Self {time_control: self.time_control, ..settings})
+            .collect()
+    }
+
+    /// Create the rules for a new game with these settings
+    pub fn rules(&self) -> Box<dyn Rules> {
+        match self.variant {
+            Variant::Standard => Box::new(Standard::new(self.width, self.height, self.win_length)),
+            Variant::Ultimate => Box::new(Ultimate::new()),
+            Variant::Gravity => Box::new(Gravity::new(self.width, self.height, self.win_length)),
+            Variant::Misere => Box::new(Misere::new(self.width, self.height, self.win_length)),
+            Variant::Notakto => Box::new(Notakto::new((self.width / ultimate::SUB_SIZE).max(1))),
+            Variant::Qubic => Box::new(Qubic::new()),
+            Variant::Gomoku => Box::new(Gomoku::new(GomokuRule::Standard)),
+            Variant::Freestyle => Box::new(Gomoku::new(GomokuRule::Freestyle)),
+            Variant::Renju => Box::new(Gomoku::new(GomokuRule::Renju)),
+            Variant::Wild => Box::new(Wild::new(self.width, self.height, self.win_length)),
+            Variant::Quantum => Box::new(Quantum::new()),
+            Variant::OrderChaos => Box::new(OrderChaos::new()),
+        }
+    }
+
+    /// Create a new game with these settings, see Game::new for how the players are used
+    /// In Order and Chaos the first player plays Order and everyone else plays Chaos
+    pub fn new_game<T: PartialEq + Copy>(&self, players: Vec<Player<T>>, first: usize) -> Game<T, Box<dyn Rules>> {
+        let players = match self.variant {
+            Variant::OrderChaos => players.into_iter()
+                .enumerate()
+                .map(|(i, player)| {
+                    let role = if i == first {Role::Order} else {Role::Chaos};
+                    Player::with_role(player.mark, player.id, role)
+                })
+                .collect(),
+            _ => players,
+        };
+        let mut game = Game::with_rules(players, first, self.rules());
+        if let Some(control) = self.time_control {
+            game.start_clock(control);
+        }
+        game
+    }
+
+    /// Create a rematch of a game with the same players and marks, the next player in the turn order going first
+    /// With two players this swaps who goes first, and in Order and Chaos who plays Order
+    pub fn rematch<T: PartialEq + Copy, R: Rules + Clone>(&self, game: &Game<T, R>) -> Game<T, Box<dyn Rules>> {
+        let players = game.players.iter()
+            .map(|player| Player::new(player.mark, player.id))
+            .collect::<Vec<_>>();
+        let first = (game.first_player() + 1) % players.len();
+        self.new_game(players, first)
+    }
+}
+
+/// Describes the settings of variants that can't be changed, None if they can
+fn fixed_settings(variant: Variant) -> Option<&'static str> {
+    match variant {
+        Variant::Ultimate => Some("ultimate games are always two players on a 9x9 board"),
+        Variant::Qubic => Some("Qubic games are always two players on a 4x4x4 cube"),
+        Variant::Quantum => Some("quantum games are always two players on a 3x3 board"),
+        Variant::OrderChaos => Some("Order and Chaos games are always two players on a 6x6 board"),
+        Variant::Gomoku | Variant::Freestyle | Variant::Renju => {
+            Some("Gomoku games are always two players on a 15x15 board")
+        },
+        _ => None,
+    }
+}
+
+impl Default for GameSettings {
+    /// Settings for a standard two player 3x3 game
+    fn default() -> Self {
+        Self {
+            variant: Variant::Standard,
+            players: NUM_PLAYERS,
+            width: DEFAULT_BOARD_SIZE,
+            height: DEFAULT_BOARD_SIZE,
+            depth: 1,
+            win_length: DEFAULT_BOARD_SIZE,
+            time_control: None,
+        }
+    }
+}
+
+/// Represents a tictactoe game played with some rules
+/// Standard rules are used unless other rules are given
+pub struct Game<T, R = Standard> {
     players: Vec<Player<T>>,
-    board: Board,
+    start: R,
+    rules: R,
     curr_player: usize,
     ended: bool,
+    result: Option<GameResult>,
+    left: bool,
+    history: Vec<Move<T>>,
+    undone: Vec<Move<T>>,
+    clock: Option<Clock>,
+    //indexes of the players agreeing to a draw, empty unless someone has offered one
+    draw_offer: Vec<usize>,
+    //indexes of the players wanting a rematch once the game is over
+    rematch_offer: Vec<usize>,
+    //number of games in a match offered once the game is over, with the indexes of the players agreeing to it
+    match_offer: Option<(usize, Vec<usize>)>,
 }
 
 impl <T: PartialEq + Copy> Game<T> {
     /// Create a new game from a vector of players and the first player
+    /// Players take turns in the order of the vector, starting with the first player
     /// Players have a generic type that indicates what type is their identification
-    pub fn new(players: Vec<Player<T>>, first: usize) -> Self {
+    /// The board is width by height with win_length marks in a row needed to win
+    pub fn new(players: Vec<Player<T>>, first: usize, width: usize, height: usize, win_length: usize) -> Self {
+        Self::with_rules(players, first, Standard::new(width, height, win_length))
+    }
+}
+
+impl <T: PartialEq + Copy, R: Rules + Clone> Game<T, R> {
+    /// Create a new game played with some rules, which start off from the state they're given in
+    pub fn with_rules(players: Vec<Player<T>>, first: usize, rules: R) -> Self {
         Self {
-            players: players,
-            board: Board::new(),
+            players,
+            start: rules.clone(),
+            rules,
             curr_player: first,
             ended: false,
+            result: None,
+            left: false,
+            history: Vec::new(),
+            undone: Vec::new(),
+            clock: None,
+            draw_offer: Vec::new(),
+            rematch_offer: Vec::new(),
+            match_offer: None,
         }
     }
 
+    /// Gives every player a clock with the same time, starting the current player's clock
+    pub fn start_clock(&mut self, control: TimeControl) {
+        let mut clock = Clock::new(control, self.players.len());
+        clock.switch(self.curr_player, Instant::now());
+        self.clock = Some(clock);
+    }
+
     /// Gets a reference to the player that's currently playing
     pub fn get_curr_player(&self) -> &Player<T> {
         &self.players[self.curr_player]
     }
 
-    /// Gets whether a move from a player is a valid move
-    pub fn can_move(&self, square: &Square, player_id: T) -> bool {
-        if !self.ended && self.get_curr_player().id() == player_id {
-            let pos = self.board.get_pos_coords(square.x, square.y);
-            if pos.is_some() {
-                if pos.unwrap() == Mark::Empty {
-                    return true;
-                }
-            }
+    /// Gets whether the game has ended
+    pub fn has_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Checks whether a move from a player is a valid move, giving the reason if it isn't
+    pub fn can_move(&self, square: &Square, player_id: T) -> Result<(), MoveError> {
+        self.check_turn(player_id)?;
+        if self.rules.pending_collapse().is_some() {
+            return Err(MoveError::MustCollapse);
+        }
+
+        self.rules.check_move(square)
+    }
+
+    /// Checks whether a player can collapse an entanglement into a square, only quantum games have these
+    /// The collapse is then made with make_move
+    pub fn can_collapse(&self, square: &Square, player_id: T) -> Result<(), MoveError> {
+        self.check_turn(player_id)?;
+        if self.rules.pending_collapse().is_none() {
+            return Err(MoveError::NothingToCollapse);
+        }
+
+        self.rules.check_move(square)
+    }
+
+    /// Checks that a player is in the game and it's their turn
+    fn check_turn(&self, player_id: T) -> Result<(), MoveError> {
+        if self.players.iter().all(|player| player.id != player_id) {
+            return Err(MoveError::UnknownPlayer);
+        }
+        if self.ended {
+            return Err(MoveError::GameOver);
+        }
+        if self.get_curr_player().id() != player_id {
+            return Err(MoveError::NotYourTurn);
         }
-        
-        false
+        Ok(())
+    }
+
+    /// Checks that a player is in the game and it hasn't ended, giving the index of the player
+    fn check_playing(&self, player_id: T) -> Result<usize, RequestError> {
+        let index = self.players.iter()
+            .position(|player| player.id == player_id)
+            .ok_or(RequestError::UnknownPlayer)?;
+        if self.ended {
+            return Err(RequestError::GameOver);
+        }
+        Ok(index)
+    }
+
+    /// Checks whether a move placing a chosen mark is valid, only some variants let players choose
+    pub fn can_move_with(&self, square: &Square, player_id: T, piece: Mark) -> Result<(), MoveError> {
+        self.can_move(square, player_id)?;
+        self.rules.check_piece(self.get_curr_player().mark(), piece)
+    }
+
+    /// Gets the square a move in a column would land in, for variants with gravity
+    pub fn column_square(&self, column: usize) -> Result<Square, MoveError> {
+        self.rules.column_square(column)
     }
 
     /// Makes the move using the current player
     /// Assumes can_move has been called
+    /// Any undone moves can't be redone after this
     pub fn make_move(&mut self, square: &Square) -> Option<GameResult> {
+        let mark = self.get_curr_player().mark();
+        self.make_move_with(square, mark)
+    }
+
+    /// Makes the move using the current player, placing a chosen mark
+    /// Assumes can_move_with has been called
+    /// The mover's clock stops and the next player's starts, the game should be checked with check_flag first
+    pub fn make_move_with(&mut self, square: &Square, piece: Mark) -> Option<GameResult> {
+        let now = Instant::now();
         let player = &self.players[self.curr_player];
-        self.board.set_pos(square.x, square.y, player.mark);
-        self.curr_player = (self.curr_player + 1) % NUM_PLAYERS;
+        let new_move = Move {
+            square: *square,
+            mark: player.mark,
+            piece: self.rules.placed_piece(piece),
+            player_id: player.id,
+            player: self.curr_player,
+            time: SystemTime::now(),
+        };
+        self.undone.clear();
+        //moving instead of answering a draw offer turns it down
+        if !self.draw_offer.contains(&new_move.player) {
+            self.draw_offer.clear();
+        }
+        let result = self.apply(new_move);
+
+        if let Some(clock) = self.clock.as_mut() {
+            match result {
+                Some(_) => clock.stop(now),
+                None => clock.switch(self.curr_player, now),
+            }
+        }
+        result
+    }
+
+    /// Ends the game if the current player has run out of time, giving the result if it ended
+    pub fn check_flag(&mut self) -> Option<GameResult> {
+        let now = Instant::now();
+        let clock = self.clock.as_mut()?;
+        if self.ended || !clock.flag_fallen(now) {
+            return None;
+        }
+
+        let result = GameResult::OutOfTime {loser: self.players[self.curr_player].mark};
+        self.end(result.clone());
+        Some(result)
+    }
+
+    /// Ends the game with a player resigning, which they can do on anyone's turn
+    pub fn resign(&mut self, player_id: T) -> Result<GameResult, RequestError> {
+        let index = self.check_playing(player_id)?;
+
+        let result = GameResult::Resigned {loser: self.players[index].mark};
+        self.end(result.clone());
+        Ok(result)
+    }
+
+    /// Offers a draw, or agrees to one if someone else has already offered
+    /// Gives the result if everyone has now agreed, which ends the game
+    pub fn offer_draw(&mut self, player_id: T) -> Result<Option<GameResult>, RequestError> {
+        let index = self.check_playing(player_id)?;
+        if self.draw_offer.contains(&index) {
+            return Err(RequestError::AlreadyOffered);
+        }
+        self.agree_draw(index)
+    }
 
-        //check if someone won
-        let result = self.board.game_over();
-        if result.is_some() {self.ended = true}
+    /// Agrees to a draw someone else has offered
+    /// Gives the result if everyone has now agreed, which ends the game
+    pub fn accept_draw(&mut self, player_id: T) -> Result<Option<GameResult>, RequestError> {
+        let index = self.check_playing(player_id)?;
+        if self.draw_offer.is_empty() || self.draw_offer.contains(&index) {
+            return Err(RequestError::NoDrawOffer);
+        }
+        self.agree_draw(index)
+    }
+
+    /// Turns down a draw offer, or takes back a player's own offer
+    pub fn decline_draw(&mut self, player_id: T) -> Result<(), RequestError> {
+        self.check_playing(player_id)?;
+        if self.draw_offer.is_empty() {
+            return Err(RequestError::NoDrawOffer);
+        }
+        self.draw_offer.clear();
+        Ok(())
+    }
+
+    /// Adds a player to those agreeing to a draw, ending the game once everyone has
+    fn agree_draw(&mut self, index: usize) -> Result<Option<GameResult>, RequestError> {
+        self.draw_offer.push(index);
+        if self.draw_offer.len() < self.players.len() {
+            return Ok(None);
+        }
+
+        self.end(GameResult::DrawAgreed);
+        Ok(Some(GameResult::DrawAgreed))
+    }
+
+    /// Gets the marks of the players agreeing to a draw, empty unless one has been offered
+    pub fn draw_offer(&self) -> Vec<Mark> {
+        self.draw_offer.iter().map(|index| self.players[*index].mark).collect()
+    }
+
+    /// Asks for a rematch once the game is over, or agrees to one if someone else has already asked
+    /// Gives whether everyone now wants one, the rematch itself is made with GameSettings::rematch
+    pub fn request_rematch(&mut self, player_id: T) -> Result<bool, RequestError> {
+        let index = self.check_finished(player_id)?;
+        if self.rematch_offer.contains(&index) {
+            return Err(RequestError::AlreadyRequested);
+        }
+        self.rematch_offer.push(index);
+        Ok(self.rematch_offer.len() == self.players.len())
+    }
+
+    /// Agrees to a rematch someone else has asked for, giving whether everyone now wants one
+    pub fn accept_rematch(&mut self, player_id: T) -> Result<bool, RequestError> {
+        let index = self.check_finished(player_id)?;
+        if self.rematch_offer.is_empty() || self.rematch_offer.contains(&index) {
+            return Err(RequestError::NoRematchRequest);
+        }
+        self.rematch_offer.push(index);
+        Ok(self.rematch_offer.len() == self.players.len())
+    }
+
+    /// Gets the marks of the players wanting a rematch, empty unless one has been asked for
+    pub fn rematch_offer(&self) -> Vec<Mark> {
+        self.rematch_offer.iter().map(|index| self.players[*index].mark).collect()
+    }
+
+    /// Offers to play a match of some number of games once the game is over, see series::Match
+    /// Offering the same number of games someone else has offered agrees to it, a different number replaces their offer
+    /// Gives the number of games if everyone now agrees
+    pub fn offer_match(&mut self, player_id: T, games: usize) -> Result<Option<usize>, RequestError> {
+        let index = self.check_finished(player_id)?;
+        if !MATCH_LENGTHS.contains(&games) {
+            return Err(RequestError::InvalidMatchLength);
+        }
+        match self.match_offer.as_ref() {
+            Some((offered, agreed)) if *offered == games && agreed.contains(&index) => Err(RequestError::AlreadyRequested),
+            Some((offered, _)) if *offered == games => self.agree_match(index),
+            _ => {
+                self.match_offer = Some((games, Vec::new()));
+                self.agree_match(index)
+            },
+        }
+    }
+
+    /// Agrees to a match someone else has offered, giving the number of games if everyone now agrees
+    pub fn accept_match(&mut self, player_id: T) -> Result<Option<usize>, RequestError> {
+        let index = self.check_finished(player_id)?;
+        match self.match_offer.as_ref() {
+            Some((_, agreed)) if !agreed.contains(&index) => self.agree_match(index),
+            _ => Err(RequestError::NoMatchOffer),
+        }
+    }
+
+    /// Adds a player to those agreeing to the match that's been offered
+    fn agree_match(&mut self, index: usize) -> Result<Option<usize>, RequestError> {
+        let players = self.players.len();
+        let (games, agreed) = self.match_offer.as_mut().ok_or(RequestError::NoMatchOffer)?;
+        agreed.push(index);
+        Ok(Some(*games).filter(|_| agreed.len() == players))
+    }
+
+    /// Gets the number of games in the match that's been offered and the marks of the players agreeing to it
+    pub fn match_offer(&self) -> Option<(usize, Vec<Mark>)> {
+        self.match_offer.as_ref()
+            .map(|(games, agreed)| (*games, agreed.iter().map(|index| self.players[*index].mark).collect()))
+    }
+
+    /// Checks that a player is in the game, it's over and nobody has left, giving the index of the player
+    fn check_finished(&self, player_id: T) -> Result<usize, RequestError> {
+        let index = self.players.iter()
+            .position(|player| player.id == player_id)
+            .ok_or(RequestError::UnknownPlayer)?;
+        if self.left {
+            return Err(RequestError::PlayerLeft);
+        }
+        if !self.ended {
+            return Err(RequestError::GameNotOver);
+        }
+        Ok(index)
+    }
+
+    /// Ends the game off the board, stopping the clocks
+    fn end(&mut self, result: GameResult) {
+        self.ended = true;
+        self.result = Some(result);
+        self.draw_offer.clear();
+        //moves taken back before the game ended can't be played into it
+        self.undone.clear();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop(Instant::now());
+        }
+    }
+
+    /// Gets the time control of the game, None if it doesn't have clocks
+    pub fn time_control(&self) -> Option<TimeControl> {
+        self.clock.as_ref().map(|clock| clock.control())
+    }
+
+    /// Gets how long each player has left, None if the game doesn't have clocks
+    pub fn clock_times(&self) -> Option<Vec<ClockTime>> {
+        let marks = self.players.iter().map(|player| player.mark).collect::<Vec<_>>();
+        self.clock.as_ref().map(|clock| clock.times(&marks, Instant::now()))
+    }
+
+    /// Takes back the last move, making it that player's turn again
+    /// Any draw offer is dropped and the clock goes back to that player, without giving back the time they used
+    /// Returns the move taken back, or None if there are no moves, a player has left or the game ended off the board
+    pub fn undo(&mut self) -> Option<&Move<T>> {
+        if self.left {
+            return None;
+        }
+        //a game that was resigned, agreed drawn or lost on time didn't end with a move, so there's nothing to take back
+        if self.result.as_ref().is_some_and(|result| result.reason() != EndReason::Board) {
+            return None;
+        }
+        let last = self.history.pop()?;
+
+        //rules can keep more than the board so replay the game without the last move
+        self.rules = self.start.clone();
+        for played in self.history.iter() {
+            self.rules.apply_piece(&played.square, played.mark, played.piece);
+        }
+        self.curr_player = last.player;
+        //games that end off the board can't be undone, so it can only have ended on the last move
+        self.ended = false;
+        self.result = None;
+        self.draw_offer.clear();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.hand_to(last.player, Instant::now());
+        }
+
+        self.undone.push(last);
+        self.undone.last()
+    }
+
+    /// Plays the last move that was taken back again
+    /// Returns the move and the result if it ended the game, or None if there's nothing to redo, a player has left or the game is over
+    pub fn redo(&mut self) -> Option<(&Move<T>, Option<GameResult>)> {
+        if self.left || self.ended {
+            return None;
+        }
+        let next = self.undone.pop()?;
+        let result = self.apply(next);
+        if let Some(clock) = self.clock.as_mut() {
+            match result {
+                Some(_) => clock.stop(Instant::now()),
+                None => clock.hand_to(self.curr_player, Instant::now()),
+            }
+        }
+        Some((self.history.last().unwrap(), result))
+    }
+
+    /// Puts a move on the board and into the history, moving on to the next player
+    fn apply(&mut self, new_move: Move<T>) -> Option<GameResult> {
+        self.rules.apply_piece(&new_move.square, new_move.mark, new_move.piece);
+        self.curr_player = self.rules.next_player(new_move.player, self.players.len());
+        self.history.push(new_move);
+
+        let result = self.rules.result();
+        if result.is_some() {
+            self.ended = true;
+            self.result = result.clone();
+        }
 
         result
     }
 
+    /// Gets how the game finished on the board, None if it hasn't or a player left
+    pub fn result(&self) -> Option<&GameResult> {
+        self.result.as_ref()
+    }
+
+    /// Gets all the moves made so far in order
+    pub fn history(&self) -> &[Move<T>] {
+        &self.history
+    }
+
+    /// Gets an iterator over the board after each move, starting with the empty board
+    pub fn positions(&self) -> Positions<'_, T, R> {
+        Positions {
+            rules: Some(self.start.clone()),
+            moves: self.history.iter(),
+        }
+    }
+
     /// Gets a vector of player ids in the game
     pub fn get_player_ids(&self) -> Vec<T> {
         let mut ids = Vec::new();
@@ -65,6 +779,42 @@ impl <T: PartialEq + Copy> Game<T> {
         ids
     }
 
+    /// Gets the ids of the players in the order they take turns, starting with the first player
+    pub fn get_turn_order(&self) -> Vec<T> {
+        let first = self.first_player();
+        (0..self.players.len())
+            .map(|i| self.players[(first + i) % self.players.len()].id)
+            .collect()
+    }
+
+    /// Gets the index of the player who moved first, or is about to
+    fn first_player(&self) -> usize {
+        match self.history.first() {
+            Some(first) => first.player,
+            None => self.curr_player,
+        }
+    }
+
+    /// Gets the id of the player that won the game, if someone won
+    pub fn get_winner(&self) -> Option<T> {
+        let player = match self.result.as_ref()? {
+            GameResult::Won {winner, ..} => self.players.iter().find(|player| player.mark == *winner),
+            //with two players whoever didn't make the line, run out of time or resign won
+            GameResult::Lost {loser, ..} | GameResult::OutOfTime {loser} | GameResult::Resigned {loser}
+                if self.players.len() == 2 => {
+                self.players.iter().find(|player| player.mark != *loser)
+            },
+            _ => None,
+        };
+        player.map(|player| player.id)
+    }
+
+    /// Gets the id of the player that lost the game by making a line, running out of time or resigning
+    pub fn get_loser(&self) -> Option<T> {
+        let mark = self.result.as_ref()?.loser()?;
+        self.players.iter().find(|player| player.mark == mark).map(|player| player.id)
+    }
+
     /// Gets a mark a player uses
     pub fn get_player_mark(&self, id: T) -> Option<Mark> {
         for player in self.players.iter() {
@@ -75,45 +825,337 @@ impl <T: PartialEq + Copy> Game<T> {
         None
     }
 
+    /// Gets the role a player has, None if the variant doesn't give players roles
+    pub fn get_player_role(&self, id: T) -> Option<Role> {
+        self.players.iter().find(|player| player.id == id).and_then(|player| player.role)
+    }
+
+    /// Gets a reference to the board of the game
+    pub fn board(&self) -> &Board {
+        self.rules.board()
+    }
+
+    /// Gets the rules the game is played with
+    pub fn rules(&self) -> &R {
+        &self.rules
+    }
+
+    /// Gets which kind of game is being played
+    pub fn variant(&self) -> Variant {
+        self.rules.variant()
+    }
+
+    /// Gets the sub-boards the current player can move in, None if the game doesn't have sub-boards
+    pub fn playable_boards(&self) -> Option<Vec<Square>> {
+        self.rules.playable_boards()
+    }
+
+    /// Gets the squares the current player has to pick between to collapse an entanglement, if there is one
+    pub fn pending_collapse(&self) -> Option<Vec<Square>> {
+        self.rules.pending_collapse()
+    }
+
+    /// Gets every mark placed so far in a quantum game, None if the game isn't quantum
+    pub fn quantum_marks(&self) -> Option<Vec<QuantumMark>> {
+        self.rules.quantum_marks()
+    }
+
     /// If a player leaves, just end the game early
     pub fn player_left(&mut self) {
         self.ended = true;
+        self.left = true;
+        if let Some(clock) = self.clock.as_mut() {
+            clock.stop(Instant::now());
+        }
+    }
+}
+
+/// A move made by a player in a game
+#[derive(Debug, Clone)]
+pub struct Move<T> {
+    square: Square,
+    mark: Mark,
+    piece: Mark,
+    player_id: T,
+    player: usize,
+    time: SystemTime,
+}
+
+impl<T: Copy> Move<T> {
+    /// Get the square the move was made in
+    pub fn square(&self) -> Square {
+        self.square
+    }
+
+    /// Get the mark of the player that made the move
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Get the mark that was placed, which is the player's own mark unless the variant lets them choose
+    pub fn piece(&self) -> Mark {
+        self.piece
+    }
+
+    /// Get the id of the player that made the move
+    pub fn player_id(&self) -> T {
+        self.player_id
+    }
+
+    /// Get the time the move was made
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+/// Iterator over the positions of a game, made by replaying its moves
+pub struct Positions<'a, T, R> {
+    rules: Option<R>,
+    moves: std::slice::Iter<'a, Move<T>>,
+}
+
+impl<'a, T, R: Rules> Iterator for Positions<'a, T, R> {
+    type Item = Board;
+
+    fn next(&mut self) -> Option<Board> {
+        let rules = self.rules.as_mut()?;
+        let board = rules.board().clone();
+        match self.moves.next() {
+            Some(next) => rules.apply_piece(&next.square, next.mark, next.piece),
+            None => self.rules = None,
+        }
+        Some(board)
     }
 }
 
+/// Reasons a move can't be made
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum MoveError {
+    NotYourTurn,
+    OutOfBounds,
+    Occupied,
+    GameOver,
+    UnknownPlayer,
+    NotInGame,
+    WrongBoard,
+    NotLowest,
+    ColumnFull,
+    NeedsSquare,
+    DoubleThree,
+    DoubleFour,
+    Overline,
+    WrongMark,
+    SameSquare,
+    MustCollapse,
+    NothingToCollapse,
+    WrongCollapse,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            MoveError::NotYourTurn => "it isn't your turn",
+            MoveError::OutOfBounds => "the square isn't on the board",
+            MoveError::Occupied => "the square already has a mark",
+            MoveError::GameOver => "the game is over",
+            MoveError::UnknownPlayer => "the player isn't in this game",
+            MoveError::NotInGame => "the player isn't in a game",
+            MoveError::WrongBoard => "the square isn't on a sub-board that can be played in",
+            MoveError::NotLowest => "the square isn't the lowest empty one in its column",
+            MoveError::ColumnFull => "the column is full",
+            MoveError::NeedsSquare => "this variant needs a square, not just a column",
+            MoveError::DoubleThree => "the first player can't make two threes at once in Renju",
+            MoveError::DoubleFour => "the first player can't make two fours at once in Renju",
+            MoveError::Overline => "the first player can't make more than five in a row in Renju",
+            MoveError::WrongMark => "players can only place their own mark in this variant",
+            MoveError::SameSquare => "both halves of a quantum move have to be in different squares",
+            MoveError::MustCollapse => "the last move made a cycle that has to be collapsed first",
+            MoveError::NothingToCollapse => "there's no cycle to collapse",
+            MoveError::WrongCollapse => "the square isn't one of the two the last move can collapse into",
+        };
+        write!(f, "Invalid move, {}", reason)
+    }
+}
+
+impl Error for MoveError {}
+
+/// Reasons a request that isn't a move, like offering a draw or asking for a rematch, can't be made
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum RequestError {
+    NotInGame,
+    InGame,
+    UnknownPlayer,
+    GameOver,
+    GameNotOver,
+    PlayerLeft,
+    AlreadyOffered,
+    NoDrawOffer,
+    AlreadyRequested,
+    NoRematchRequest,
+    InvalidMatchLength,
+    NoMatchOffer,
+    NoSuchGame,
+    Spectating,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            RequestError::NotInGame => "you aren't in a game",
+            RequestError::InGame => "you're playing in a game that hasn't finished",
+            RequestError::UnknownPlayer => "the player isn't in this game",
+            RequestError::GameOver => "the game is over",
+            RequestError::GameNotOver => "the game hasn't finished yet",
+            RequestError::PlayerLeft => "a player has left the game",
+            RequestError::AlreadyOffered => "you've already offered or agreed to a draw",
+            RequestError::NoDrawOffer => "nobody else has offered a draw",
+            RequestError::AlreadyRequested => "you've already asked for a rematch or match",
+            RequestError::NoRematchRequest => "nobody else has asked for a rematch",
+            RequestError::InvalidMatchLength => "matches can only be best of 3, 5 or 7",
+            RequestError::NoMatchOffer => "nobody else has offered a match",
+            RequestError::NoSuchGame => "there's no game going on with that number",
+            RequestError::Spectating => "spectators can't play in the game they're watching",
+        };
+        write!(f, "{}", reason)
+    }
+}
+
+impl Error for RequestError {}
+
 /// Enum for a result of a game
-#[derive(Debug, Clone, Copy)]
+/// A win has the mark of the player who won and the line that won it
+/// In misère games the player who made the line lost instead
+/// A player who runs out of time on their clock or resigns loses without a line,
+/// and players can agree to a draw instead of finishing the game
+#[derive(Debug, Clone)]
 pub enum GameResult {
-    CrossWon,
-    NoughtWon,
+    Won {winner: Mark, line: WinningLine},
+    Lost {loser: Mark, line: WinningLine},
+    OutOfTime {loser: Mark},
+    Resigned {loser: Mark},
     Draw,
+    DrawAgreed,
 }
 
 impl GameResult {
-    /// Get a game result from the mark winner
-    fn from_mark(mark: Mark) -> GameResult {
-        match mark {
-            Mark::Cross => GameResult::CrossWon,
-            Mark::Nought => GameResult::NoughtWon,
-            Mark::Empty => panic!("Invalid"),
+    /// Gets the mark that won, if there was a winner
+    pub fn winner(&self) -> Option<Mark> {
+        match self {
+            GameResult::Won {winner, ..} => Some(*winner),
+            _ => None,
         }
     }
+
+    /// Gets the mark of the player that lost by making a line, running out of time or resigning, if there was one
+    pub fn loser(&self) -> Option<Mark> {
+        match self {
+            GameResult::Lost {loser, ..} | GameResult::OutOfTime {loser} | GameResult::Resigned {loser} => Some(*loser),
+            _ => None,
+        }
+    }
+
+    /// Gets whether a player with a mark won
+    /// When a player loses, everyone except that player has won
+    pub fn won_by(&self, mark: Mark) -> bool {
+        match self {
+            GameResult::Won {winner, ..} => *winner == mark,
+            GameResult::Lost {loser, ..} | GameResult::OutOfTime {loser} | GameResult::Resigned {loser} => *loser != mark,
+            GameResult::Draw | GameResult::DrawAgreed => false,
+        }
+    }
+
+    /// Gets the line that ended the game, if there was one
+    /// Some wins don't have a line, like Chaos filling the board in Order and Chaos
+    pub fn line(&self) -> Option<&WinningLine> {
+        match self {
+            GameResult::Won {line, ..} | GameResult::Lost {line, ..} => Some(line).filter(|line| !line.squares.is_empty()),
+            _ => None,
+        }
+    }
+
+    /// Gets whether nobody won, either on the board or by agreeing to a draw
+    pub fn is_draw(&self) -> bool {
+        matches!(self, GameResult::Draw | GameResult::DrawAgreed)
+    }
+
+    /// Gets how the game came to an end
+    pub fn reason(&self) -> EndReason {
+        match self {
+            GameResult::Won {..} | GameResult::Lost {..} | GameResult::Draw => EndReason::Board,
+            GameResult::OutOfTime {..} => EndReason::OutOfTime,
+            GameResult::Resigned {..} => EndReason::Resigned,
+            GameResult::DrawAgreed => EndReason::DrawAgreed,
+        }
+    }
+
+    /// Gets whether the game ended with a line that lost, like in misère games
+    pub fn is_line_lost(&self) -> bool {
+        matches!(self, GameResult::Lost {..})
+    }
+}
+
+/// Ways a game can end, sent to players along with the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum EndReason {
+    //a line was made or the board filled up
+    Board,
+    OutOfTime,
+    Resigned,
+    DrawAgreed,
+}
+
+/// The squares of a line that won a game
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WinningLine {
+    squares: Vec<Square>,
+    last_move: Option<Square>,
+}
+
+impl WinningLine {
+    /// Create a line from its squares and the move that completed it
+    pub(crate) fn new(squares: Vec<Square>, last_move: Option<Square>) -> Self {
+        Self {squares, last_move}
+    }
+
+    /// Gets the squares in the line in order from one end to the other
+    pub fn squares(&self) -> &[Square] {
+        &self.squares
+    }
+
+    /// Gets the move that completed the line, if it's known
+    pub fn last_move(&self) -> Option<Square> {
+        self.last_move
+    }
 }
 
-/// Defines a player with a mark and an id
+/// Defines a player with a mark and an id, and a role in variants where players have different goals
 /// The id is a generic type
 #[derive(Debug, Clone)]
 pub struct Player<T> {
     mark: Mark,
     id: T,
+    role: Option<Role>,
 }
 
 impl<T: PartialEq + Copy> Player<T> {
     /// Create new player
     pub fn new(mark: Mark, id: T) -> Self {
         Self {
-            mark: mark,
-            id: id,
+            mark,
+            id,
+            role: None,
+        }
+    }
+
+    /// Create new player with a role
+    pub fn with_role(mark: Mark, id: T, role: Role) -> Self {
+        Self {
+            mark,
+            id,
+            role: Some(role),
         }
     }
 
@@ -121,131 +1163,596 @@ impl<T: PartialEq + Copy> Player<T> {
     pub fn id(&self) -> T {
         self.id
     }
+
+    /// Get mark of player
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Get role of player, None unless the variant gives players different goals
+    pub fn role(&self) -> Option<Role> {
+        self.role
+    }
+}
+
+/// Roles for variants where players have different goals instead of all trying to make a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Role {
+    //tries to make exactly five in a row
+    Order,
+    //tries to fill the board without one
+    Chaos,
 }
 
-/// Mark on a noughts and crosses boar
-#[derive(PartialEq, Clone, Copy, Debug)]
+/// Mark on a noughts and crosses board
+/// Games with more than two players use other marks for the extra players
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 #[derive(Serialize, Deserialize)]
 pub enum Mark {
     Nought,
     Cross,
+    Other(u8),
     Empty,
 }
 
+impl Mark {
+    /// Gets the mark of the other player in a two player game, any other mark stays the same
+    pub fn opponent(self) -> Mark {
+        match self {
+            Mark::Nought => Mark::Cross,
+            Mark::Cross => Mark::Nought,
+            other => other,
+        }
+    }
+
+    /// Gets the mark for the player at an index, crosses first then noughts then the others
+    pub fn from_index(index: usize) -> Mark {
+        match index {
+            0 => Mark::Cross,
+            1 => Mark::Nought,
+            _ => Mark::Other((index - 2) as u8),
+        }
+    }
+
+    /// Gets the index of a mark, the opposite of from_index
+    /// Empty doesn't have an index
+    pub fn index(self) -> Option<usize> {
+        match self {
+            Mark::Cross => Some(0),
+            Mark::Nought => Some(1),
+            Mark::Other(other) => Some(other as usize + 2),
+            Mark::Empty => None,
+        }
+    }
+}
+
 /// Represents a board
-#[derive(Debug)]
+/// The board is `width` by `height` and a player needs `win_length` marks in a row to win
+/// Stored as a bitboard for each mark so checking lines is fast
+#[derive(Debug, Clone)]
 pub struct Board {
     width: usize,
     height: usize,
-    array: [Mark; BOARD_SIZE*BOARD_SIZE],
+    depth: usize,
+    win_length: usize,
+    marks: Vec<Bitboard>,
+    filled: usize,
+    hash: u64,
+    lines: Arc<WinLines>,
 }
 
 impl Board {
-    /// Create a new board
-    pub fn new() -> Self {
+    /// Create a new empty board with the given dimensions and number of marks in a row needed to win
+    /// Panics if the win length is zero or can't fit on the board
+    pub fn new(width: usize, height: usize, win_length: usize) -> Self {
+        Self::new_3d(width, height, 1, win_length)
+    }
+
+    /// Create a new empty board with layers stacked on top of each other, a depth of 1 is a flat board
+    /// Lines can go through the layers as well as along them
+    /// Panics if the depth is zero, or the win length is zero or can't fit on the board
+    pub fn new_3d(width: usize, height: usize, depth: usize, win_length: usize) -> Self {
+        assert!(depth > 0, "Invalid depth");
+        assert!(win_length > 0 && win_length <= width.max(height).max(depth), "Invalid win length");
         Self {
-            width: BOARD_SIZE,
-            height: BOARD_SIZE,
-            array: [Mark::Empty; BOARD_SIZE*BOARD_SIZE],
+            width,
+            height,
+            depth,
+            win_length,
+            marks: vec![Bitboard::new(width * height * depth); NUM_PLAYERS],
+            filled: 0,
+            hash: 0,
+            lines: WinLines::new(width, height, depth, win_length),
         }
     }
 
+    /// Gets the width of the board
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Gets the height of the board
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Gets the number of layers of the board, flat boards have 1
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Gets the number of marks in a row needed to win
+    pub fn win_length(&self) -> usize {
+        self.win_length
+    }
+
+    /// Gets whether a square is within the bounds of the board
+    /// Squares without a z coord are on the bottom layer
+    pub fn contains(&self, square: &Square) -> bool {
+        self.spot(square).is_some()
+    }
+
+    /// Gets all the squares on the board that haven't been played in yet
+    pub fn empty_squares(&self) -> Vec<Square> {
+        (0..self.size())
+            .filter(|spot| self.mark_at(*spot) == Mark::Empty)
+            .map(|spot| self.square_at(spot))
+            .collect()
+    }
+
+    /// Gets the bitboards for each mark, in the order of the mark indexes
+    pub(crate) fn bitboards(&self) -> &[Bitboard] {
+        &self.marks
+    }
+
     /// Gets the contents on the board given an x and y coord of the position
     pub fn get_pos_coords(&self, x: usize, y: usize) -> Option<Mark> {
-        if x >= BOARD_SIZE || y >= BOARD_SIZE {
+        if x >= self.width || y >= self.height {
             return None;
         }
-        Some(self.array[y * BOARD_SIZE + x]) 
+        Some(self.mark_at(y * self.width + x))
     }
     
+    /// Gets the mark on every square, going along each row, then down the rows, then through the layers
+    pub fn marks(&self) -> Vec<Mark> {
+        (0..self.size()).map(|spot| self.mark_at(spot)).collect()
+    }
+
     /// Gets the contents on the board given a single coord of the position
     pub fn get_pos(&self, spot: usize) -> Option<Mark> {
-        self.get_pos_coords(spot % self.width, spot / self.width)
+        if spot >= self.size() {
+            return None;
+        }
+        Some(self.mark_at(spot))
+    }
+
+    /// Gets the contents of a square on the board, which can be on any layer
+    pub fn get_square(&self, square: &Square) -> Option<Mark> {
+        self.spot(square).map(|spot| self.mark_at(spot))
     }
     
-    /// Sets the position on a board
+    /// Sets the position on the bottom layer of a board
+    /// Panics if the position is outside the board
     pub fn set_pos(&mut self, x: usize, y: usize, new_pos: Mark) {
-        self.array[y * BOARD_SIZE + x] = new_pos;
+        self.set_square(&Square::new(x, y), new_pos);
     }
 
-    /// Checks whether the game is over, returning what the result is
-    /// Could be more cleanly implemented
-    pub fn game_over(&self) -> Option<GameResult> {
-        //check lines and diagonals
-        //first check horizontal
-        for y in 0..BOARD_SIZE {
-            let first_mark = self.array[y*3];
-            if first_mark != Mark::Empty {
-                let mut count = 1;
-                for x in 1..BOARD_SIZE {
-                    if self.array[y*3 + x] == first_mark {
-                        count += 1;
-                    }
-                }
-                if count == BOARD_SIZE {
-                    return Some(GameResult::from_mark(first_mark));
-                }
-            }
+    /// Sets a square on any layer of a board
+    /// Panics if the square is outside the board
+    pub fn set_square(&mut self, square: &Square, new_pos: Mark) {
+        let spot = self.spot(square).expect("Position out of bounds");
+
+        let old_pos = self.mark_at(spot);
+        match old_pos.index() {
+            Some(index) => self.marks[index].clear(spot),
+            None => self.filled += 1,
         }
-        //check vertical
-        for x in 0..BOARD_SIZE {
-            let first_mark = self.array[x];
-            if first_mark != Mark::Empty {
-                let mut count = 1;
-                for y in 1..BOARD_SIZE {
-                    if self.array[y*3 + x] == first_mark {
-                        count += 1;
-                    }
-                }
-                if count == BOARD_SIZE {
-                    return Some(GameResult::from_mark(first_mark));
+        match new_pos.index() {
+            Some(index) => {
+                if index >= self.marks.len() {
+                    self.marks.resize(index + 1, Bitboard::new(self.size()));
                 }
-            }
+                self.marks[index].set(spot);
+            },
+            None => self.filled -= 1,
         }
+        self.hash ^= zobrist_key(spot, old_pos) ^ zobrist_key(spot, new_pos);
+    }
 
-        //check diagonals
-        //top left to bottom right diagonal
-        let first_mark = self.array[0];
-        if first_mark != Mark::Empty {
-            let mut count = 1;
-            for x in 1..BOARD_SIZE {
-                let y = x;
-                if self.array[y*3 + x] == first_mark {
-                    count += 1;
-                }
-            }
-            if count == BOARD_SIZE {
-                return Some(GameResult::from_mark(first_mark));
+    /// Gets the Zobrist hash of the board, which is kept up to date as marks are set
+    /// Boards with the same marks in the same places always have the same hash
+    pub fn zobrist_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Gets the symmetries that map the board onto a board of the same size
+    /// Square boards have all eight, other boards only have four
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        Symmetry::ALL.iter()
+            .copied()
+            .filter(|symmetry| self.width == self.height || !symmetry.swaps_axes())
+            .collect()
+    }
+
+    /// Creates a copy of the board with a symmetry applied to it
+    /// Symmetries that swap the axes swap the width and height
+    /// Boards with layers have the symmetry applied to every layer
+    pub fn transform(&self, symmetry: Symmetry) -> Board {
+        let (width, height) = match symmetry.swaps_axes() {
+            true => (self.height, self.width),
+            false => (self.width, self.height),
+        };
+        let mut board = Board::new_3d(width, height, self.depth, self.win_length);
+        for spot in 0..self.size() {
+            let mark = self.mark_at(spot);
+            if mark != Mark::Empty {
+                let square = symmetry.apply(self.square_at(spot), self.width, self.height);
+                board.set_square(&square, mark);
             }
         }
+        board
+    }
+
+    /// Gets the canonical form of the board along with the symmetry that makes it
+    /// Boards that are rotations or reflections of each other all have the same canonical form
+    pub fn canonical(&self) -> (Board, Symmetry) {
+        self.symmetries().into_iter()
+            .map(|symmetry| (self.transform(symmetry), symmetry))
+            .min_by(|(a, _), (b, _)| a.marks.cmp(&b.marks))
+            .unwrap()
+    }
 
-        //from top right to bottom left
-        let first_mark = self.array[BOARD_SIZE-1];
-        if first_mark != Mark::Empty {
-            let mut count = 1;
-            for x in (0..BOARD_SIZE-1).rev() {
-                let y = BOARD_SIZE - 1 - x;
-                if self.array[y*3 + x] == first_mark {
-                    count += 1;
+    /// Gets the Zobrist hash of the canonical form of the board
+    /// This is the same for every rotation or reflection of the board
+    pub fn canonical_hash(&self) -> u64 {
+        self.canonical().0.hash
+    }
+
+    /// Checks whether the game is over, returning what the result is
+    pub fn game_over(&self) -> Option<GameResult> {
+        for line in self.lines.all() {
+            for (index, marks) in self.marks.iter().enumerate() {
+                if marks.contains_line(line) {
+                    let line = self.winning_line(line, None);
+                    return Some(GameResult::Won {winner: Mark::from_index(index), line});
                 }
             }
-            if count == BOARD_SIZE {
-                return Some(GameResult::from_mark(first_mark));
-            }
         }
 
-        for value in self.array.iter() {
-            if *value == Mark::Empty {
-                return None;
-            }
+        self.draw_check()
+    }
+
+    /// Checks whether the game is over after a move on the bottom layer
+    /// Only looks at lines through the square moved in, so assumes the game wasn't over before the move
+    pub fn game_over_at(&self, x: usize, y: usize) -> Option<GameResult> {
+        self.game_over_at_square(&Square::new(x, y))
+    }
+
+    /// Checks whether the game is over after a move on any layer, see game_over_at
+    /// Panics if the square is outside the board
+    pub fn game_over_at_square(&self, square: &Square) -> Option<GameResult> {
+        let spot = self.spot(square).expect("Position out of bounds");
+        let mark = self.mark_at(spot);
+        let marks = match mark.index() {
+            Some(index) => &self.marks[index],
+            None => return self.draw_check(),
+        };
+
+        if let Some(line) = self.lines.through(spot).find(|line| marks.contains_line(line)) {
+            let line = self.winning_line(line, Some(*square));
+            return Some(GameResult::Won {winner: mark, line});
         }
 
-        Some(GameResult::Draw)
+        self.draw_check()
     }
+
+    /// Makes the winning line for a line on the board
+    fn winning_line(&self, line: &Line, last_move: Option<Square>) -> WinningLine {
+        let squares = line.spots().iter()
+            .map(|spot| self.square_at(*spot))
+            .collect();
+        WinningLine {squares, last_move}
+    }
+
+    /// Gets the number of squares on the board
+    fn size(&self) -> usize {
+        self.width * self.height * self.depth
+    }
+
+    /// Gets the single coord of a square, None if it isn't on the board
+    fn spot(&self, square: &Square) -> Option<usize> {
+        let z = square.z.unwrap_or(0);
+        if square.x >= self.width || square.y >= self.height || z >= self.depth {
+            return None;
+        }
+        Some((z * self.height + square.y) * self.width + square.x)
+    }
+
+    /// Gets the square at a single coord, only giving it a z coord if the board has layers
+    fn square_at(&self, spot: usize) -> Square {
+        let square = Square::new(spot % self.width, spot / self.width % self.height);
+        match self.depth {
+            1 => square,
+            _ => Square::new_3d(square.x, square.y, spot / (self.width * self.height)),
+        }
+    }
+
+    /// Gets a draw if the board is full
+    fn draw_check(&self) -> Option<GameResult> {
+        if self.filled == self.size() {
+            Some(GameResult::Draw)
+        } else {
+            None
+        }
+    }
+
+    /// Gets the mark at a single coord, assuming it's on the board
+    fn mark_at(&self, spot: usize) -> Mark {
+        match self.marks.iter().position(|marks| marks.get(spot)) {
+            Some(index) => Mark::from_index(index),
+            None => Mark::Empty,
+        }
+    }
+}
+
+impl Default for Board {
+    /// Creates a standard 3x3 tictactoe board
+    fn default() -> Self {
+        Self::new(DEFAULT_BOARD_SIZE, DEFAULT_BOARD_SIZE, DEFAULT_BOARD_SIZE)
+    }
+}
+
+/// Gets the Zobrist key for a mark on a single coord
+/// Keys are made by mixing the coord and mark with splitmix64 so they don't need to be stored
+fn zobrist_key(spot: usize, mark: Mark) -> u64 {
+    let mark_index = match mark.index() {
+        Some(index) => index as u64 + 1,
+        None => return 0,
+    };
+    let mut z = ((spot as u64) << 8 | mark_index).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// One of the eight ways of rotating or reflecting a board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    Identity,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+    FlipDiagonal,
+    FlipAntiDiagonal,
+}
+
+impl Symmetry {
+    /// All the symmetries
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::Rotate90,
+        Symmetry::Rotate180,
+        Symmetry::Rotate270,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipVertical,
+        Symmetry::FlipDiagonal,
+        Symmetry::FlipAntiDiagonal,
+    ];
+
+    /// Gets whether the symmetry swaps the x and y axes, so only keeps square boards the same size
+    pub fn swaps_axes(self) -> bool {
+        matches!(self, Symmetry::Rotate90 | Symmetry::Rotate270 | Symmetry::FlipDiagonal | Symmetry::FlipAntiDiagonal)
+    }
+
+    /// Gets the symmetry that undoes this one
+    pub fn inverse(self) -> Symmetry {
+        match self {
+            Symmetry::Rotate90 => Symmetry::Rotate270,
+            Symmetry::Rotate270 => Symmetry::Rotate90,
+            other => other,
+        }
+    }
+
+    /// Moves a square on a board of the given size to where the symmetry puts it
+    /// Rotations are clockwise
+    pub fn apply(self, square: Square, width: usize, height: usize) -> Square {
+        let (x, y) = (square.x, square.y);
+        let (x, y) = match self {
+            Symmetry::Identity => (x, y),
+            Symmetry::Rotate90 => (height - 1 - y, x),
+            Symmetry::Rotate180 => (width - 1 - x, height - 1 - y),
+            Symmetry::Rotate270 => (y, width - 1 - x),
+            Symmetry::FlipHorizontal => (width - 1 - x, y),
+            Symmetry::FlipVertical => (x, height - 1 - y),
+            Symmetry::FlipDiagonal => (y, x),
+            Symmetry::FlipAntiDiagonal => (height - 1 - y, width - 1 - x),
+        };
+        Square {x, y, z: square.z}
+    }
+}
+
+/// A square on a board
+/// Whether it's actually on the board is checked against the board it's used with
+/// Squares on boards with layers also have a z coord, which is left out of messages when it isn't there
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Square {
     x: usize,
     y: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    z: Option<usize>,
+}
+
+impl Square {
+    /// Create a new square from x and y coords
+    pub fn new(x: usize, y: usize) -> Self {
+        Self { x, y, z: None }
+    }
+
+    /// Create a new square on a layer of a board
+    pub fn new_3d(x: usize, y: usize, z: usize) -> Self {
+        Self { x, y, z: Some(z) }
+    }
+
+    /// Get x coord of the square
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    /// Get y coord of the square
+    pub fn y(&self) -> usize {
+        self.y
+    }
+    /// Get z coord of the square, None if it isn't on a board with layers
+    pub fn z(&self) -> Option<usize> {
+        self.z
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::clock::Increment;
+
+    fn new_game(settings: GameSettings) -> Game<usize, Box<dyn Rules>> {
+        settings.new_game(vec![Player::new(Mark::Cross, 0), Player::new(Mark::Nought, 1)], 0)
+    }
+
+    /// Works out the Zobrist hash of a board from scratch
+    fn recomputed_hash(board: &Board) -> u64 {
+        (0..board.size()).fold(0, |hash, spot| hash ^ zobrist_key(spot, board.mark_at(spot)))
+    }
+
+    fn args(args: &str) -> Result<GameSettings, String> {
+        GameSettings::from_args(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn player_counts_have_to_have_marks() {
+        assert_eq!(args("2").unwrap().players, 2);
+        assert_eq!(args("10 11 11 3").unwrap().players, 10);
+        assert_eq!(args("notakto 10").unwrap().players, 10);
+        for players in ["0", "1", "11", "300", "notakto 1", "notakto 11"].iter() {
+            assert!(args(players).is_err(), "{} players", players);
+        }
+    }
+
+    #[test]
+    fn every_symmetry_has_the_same_canonical_hash() {
+        //nothing about this board is symmetric so every transform is different
+        let mut board = Board::new(4, 4, 3);
+        board.set_pos(0, 0, Mark::Cross);
+        board.set_pos(1, 0, Mark::Nought);
+        board.set_pos(3, 1, Mark::Cross);
+        board.set_pos(2, 3, Mark::Other(0));
+
+        let hash = board.canonical_hash();
+        let transforms = Symmetry::ALL.iter().map(|symmetry| board.transform(*symmetry)).collect::<Vec<_>>();
+        for transformed in transforms.iter() {
+            assert_eq!(transformed.canonical_hash(), hash);
+        }
+        let mut hashes = transforms.iter().map(|transformed| transformed.zobrist_hash()).collect::<Vec<_>>();
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), 8);
+
+        for symmetry in Symmetry::ALL.iter() {
+            let back = board.transform(*symmetry).transform(symmetry.inverse());
+            assert_eq!(back.marks(), board.marks());
+        }
+    }
+
+    #[test]
+    fn rectangles_only_use_four_symmetries() {
+        let mut board = Board::new(4, 3, 3);
+        board.set_pos(0, 0, Mark::Cross);
+        board.set_pos(3, 1, Mark::Nought);
+        assert_eq!(board.symmetries().len(), 4);
+        for symmetry in board.symmetries() {
+            assert_eq!(board.transform(symmetry).canonical_hash(), board.canonical_hash());
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_recomputed() {
+        let rng = fastrand::Rng::with_seed(8);
+        let marks = [Mark::Empty, Mark::Cross, Mark::Nought, Mark::Other(0)];
+        let mut board = Board::new_3d(4, 4, 2, 4);
+        assert_eq!(board.zobrist_hash(), 0);
+
+        //sets, overwrites and clears in any order
+        for _ in 0..500 {
+            let square = Square::new_3d(rng.usize(0..4), rng.usize(0..4), rng.usize(0..2));
+            board.set_square(&square, marks[rng.usize(0..marks.len())]);
+            assert_eq!(board.zobrist_hash(), recomputed_hash(&board));
+        }
+
+        for spot in 0..board.size() {
+            let square = board.square_at(spot);
+            board.set_square(&square, Mark::Empty);
+        }
+        assert_eq!(board.zobrist_hash(), 0);
+    }
+
+    #[test]
+    fn undo_takes_back_a_winning_move() {
+        let mut game = new_game(GameSettings::default());
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0)].iter() {
+            game.make_move(&Square::new(*x, *y));
+        }
+        assert!(game.has_ended());
+
+        assert_eq!(game.undo().map(|undone| undone.square), Some(Square::new(2, 0)));
+        assert!(!game.has_ended());
+        assert!(game.result().is_none());
+        assert_eq!(game.get_curr_player().mark(), Mark::Cross);
+    }
+
+    #[test]
+    fn undo_refuses_games_ended_off_the_board() {
+        let mut resigned = new_game(GameSettings::default());
+        resigned.make_move(&Square::new(0, 0));
+        resigned.resign(1).unwrap();
+        assert!(resigned.undo().is_none());
+        assert!(resigned.has_ended());
+        assert_eq!(resigned.result().map(|result| result.reason()), Some(EndReason::Resigned));
+        assert_eq!(resigned.history().len(), 1);
+
+        let mut drawn = new_game(GameSettings::default());
+        drawn.make_move(&Square::new(0, 0));
+        drawn.offer_draw(0).unwrap();
+        drawn.accept_draw(1).unwrap();
+        assert!(drawn.undo().is_none());
+        assert_eq!(drawn.result().map(|result| result.reason()), Some(EndReason::DrawAgreed));
+    }
+
+    #[test]
+    fn redo_refuses_games_ended_after_undo() {
+        let mut game = new_game(GameSettings::default());
+        game.make_move(&Square::new(0, 0));
+        game.undo().unwrap();
+        game.resign(1).unwrap();
+
+        assert!(game.redo().is_none());
+        assert!(game.history().is_empty());
+        assert_eq!(game.result().map(|result| result.reason()), Some(EndReason::Resigned));
+    }
+
+    #[test]
+    fn undo_drops_draw_offer_and_hands_back_clock() {
+        let control = TimeControl::new(Duration::from_secs(60), Increment::Fischer(Duration::from_secs(0)));
+        let mut game = new_game(GameSettings {time_control: Some(control), ..GameSettings::default()});
+        game.make_move(&Square::new(0, 0));
+        game.offer_draw(1).unwrap();
+
+        game.undo().unwrap();
+        assert!(game.draw_offer().is_empty());
+        let running = game.clock_times().unwrap().into_iter().find(|time| time.is_running());
+        assert_eq!(running.map(|time| time.mark()), Some(Mark::Cross));
+    }
 }
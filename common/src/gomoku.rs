@@ -0,0 +1,332 @@
+/// Gomoku, five in a row on a 15x15 board, with a few different rules for what counts as five
+/// Freestyle lets any five or more win, standard only counts exactly five,
+/// and Renju forbids the first player from making two threes, two fours or more than five in a row
+/// Only the first player is restricted in Renju and the second player wins with five or more
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant, WinningLine};
+use crate::rules::Rules;
+
+/// Width and height of a Gomoku board
+pub const SIZE: usize = 15;
+/// Number of marks in a row needed to win
+pub const WIN_LENGTH: usize = 5;
+
+/// Steps along the four directions lines can go in
+pub(crate) const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+/// How far either side of a move is looked at when checking for threes and fours
+const REACH: usize = WIN_LENGTH;
+/// Index of the move in a line of cells
+const CENTRE: usize = REACH;
+
+/// The different rules for what wins and which moves are allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GomokuRule {
+    Freestyle,
+    Standard,
+    Renju,
+}
+
+/// What is on a square near a move, from the point of view of the player moving
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cell {
+    Own,
+    Empty,
+    //the other player's mark or off the board
+    Blocked,
+}
+
+/// The squares along one direction through a move, with the move at CENTRE
+type Cells = [Cell; 2 * REACH + 1];
+
+/// Rules for Gomoku games
+#[derive(Debug, Clone)]
+pub struct Gomoku {
+    board: Board,
+    rule: GomokuRule,
+    //the first player's mark, once they've moved
+    first: Option<Mark>,
+    marks_placed: usize,
+    result: Option<GameResult>,
+}
+
+impl Gomoku {
+    /// Create the rules for an empty 15x15 game with some rules for winning
+    pub fn new(rule: GomokuRule) -> Self {
+        Self {
+            board: Board::new(SIZE, SIZE, WIN_LENGTH),
+            rule,
+            first: None,
+            marks_placed: 0,
+            result: None,
+        }
+    }
+
+    /// Gets which rules the game is played with
+    pub fn rule(&self) -> GomokuRule {
+        self.rule
+    }
+
+    /// Gets whether a mark only wins with exactly five in a row
+    fn needs_exact_five(&self, mark: Mark) -> bool {
+        match self.rule {
+            GomokuRule::Freestyle => false,
+            GomokuRule::Standard => true,
+            GomokuRule::Renju => self.first == Some(mark),
+        }
+    }
+
+    /// Checks a move by the first player in Renju, which can't make an overline, two fours or two threes
+    /// Making exactly five wins straight away so is always allowed
+    fn check_forbidden(&self, square: &Square, mark: Mark) -> Result<(), MoveError> {
+        let lines = DIRECTIONS.iter()
+            .map(|direction| cells(&self.board, square, *direction, mark))
+            .collect::<Vec<_>>();
+
+        if lines.iter().any(|line| run_length(line) == WIN_LENGTH) {
+            return Ok(());
+        }
+        if lines.iter().any(|line| run_length(line) > WIN_LENGTH) {
+            return Err(MoveError::Overline);
+        }
+        if lines.iter().map(fours).sum::<usize>() >= 2 {
+            return Err(MoveError::DoubleFour);
+        }
+        let threes = lines.iter().filter(|line| fours(line) == 0 && is_three(line)).count();
+        if threes >= 2 {
+            return Err(MoveError::DoubleThree);
+        }
+        Ok(())
+    }
+
+    /// Gets the squares of a winning row made by a move, if there is one
+    fn winning_row(&self, square: &Square, mark: Mark) -> Option<Vec<Square>> {
+        let exact = self.needs_exact_five(mark);
+        DIRECTIONS.iter()
+            .map(|direction| row_through(&self.board, square, *direction, mark))
+            .find(|row| row.len() == WIN_LENGTH || (!exact && row.len() > WIN_LENGTH))
+    }
+}
+
+impl Default for Gomoku {
+    /// Rules for a freestyle game
+    fn default() -> Self {
+        Self::new(GomokuRule::Freestyle)
+    }
+}
+
+impl Rules for Gomoku {
+    fn variant(&self) -> Variant {
+        match self.rule {
+            GomokuRule::Freestyle => Variant::Freestyle,
+            GomokuRule::Standard => Variant::Gomoku,
+            GomokuRule::Renju => Variant::Renju,
+        }
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        self.board.empty_squares().into_iter()
+            .filter(|square| self.check_move(square).is_ok())
+            .collect()
+    }
+
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        match self.board.get_square(square) {
+            None => return Err(MoveError::OutOfBounds),
+            Some(Mark::Empty) => (),
+            Some(_) => return Err(MoveError::Occupied),
+        }
+
+        //players take turns so the first player moves whenever an even number of marks are down
+        match self.first {
+            Some(first) if self.rule == GomokuRule::Renju && self.marks_placed.is_multiple_of(2) => {
+                self.check_forbidden(square, first)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_square(square, mark);
+        self.first.get_or_insert(mark);
+        self.marks_placed += 1;
+
+        self.result = match self.winning_row(square, mark) {
+            Some(row) => Some(GameResult::Won {winner: mark, line: WinningLine::new(row, Some(*square))}),
+            None if self.marks_placed == SIZE * SIZE => Some(GameResult::Draw),
+            None => None,
+        };
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
+
+/// Gets the square a number of steps from another in a direction, None if it's off the board
+fn step(board: &Board, square: &Square, (dx, dy): (isize, isize), steps: isize) -> Option<Square> {
+    let x = square.x() as isize + dx * steps;
+    let y = square.y() as isize + dy * steps;
+    if x < 0 || y < 0 || x as usize >= board.width() || y as usize >= board.height() {
+        return None;
+    }
+    Some(Square::new(x as usize, y as usize))
+}
+
+/// Gets the cells along a direction through a square as if a mark was placed on it
+fn cells(board: &Board, square: &Square, direction: (isize, isize), mark: Mark) -> Cells {
+    let mut line = [Cell::Blocked; 2 * REACH + 1];
+    for (i, cell) in line.iter_mut().enumerate() {
+        let found = step(board, square, direction, i as isize - CENTRE as isize)
+            .and_then(|near| board.get_square(&near));
+        *cell = match found {
+            _ if i == CENTRE => Cell::Own,
+            Some(Mark::Empty) => Cell::Empty,
+            Some(near) if near == mark => Cell::Own,
+            _ => Cell::Blocked,
+        };
+    }
+    line
+}
+
+/// Gets every square in the unbroken row of a mark going through a square in a direction
+pub(crate) fn row_through(board: &Board, square: &Square, direction: (isize, isize), mark: Mark) -> Vec<Square> {
+    let same = |steps: isize| step(board, square, direction, steps)
+        .and_then(|near| board.get_square(&near)) == Some(mark);
+    let back = (1..).take_while(|steps: &isize| same(-steps)).count() as isize;
+    let forward = (1..).take_while(|steps: &isize| same(*steps)).count() as isize;
+    (-back..=forward).filter_map(|steps| step(board, square, direction, steps)).collect()
+}
+
+/// Gets the length of the unbroken row through the move, as far as the cells reach
+fn run_length(line: &Cells) -> usize {
+    let back = line[..CENTRE].iter().rev().take_while(|cell| **cell == Cell::Own).count();
+    let forward = line[CENTRE + 1..].iter().take_while(|cell| **cell == Cell::Own).count();
+    back + 1 + forward
+}
+
+/// Gets every way one more mark makes exactly five with the move,
+/// as the marks already in the five and the empty square that completes it
+fn fives(line: &Cells) -> Vec<(u16, usize)> {
+    let mut found = Vec::new();
+    for start in CENTRE + 1 - WIN_LENGTH..=CENTRE {
+        let window = start..start + WIN_LENGTH;
+        let empty = window.clone().filter(|i| line[*i] == Cell::Empty).collect::<Vec<_>>();
+        let own = window.filter(|i| line[*i] == Cell::Own).fold(0u16, |mask, i| mask | 1 << i);
+        //a mark just outside the window would make it an overline instead
+        let closed = line[start - 1] != Cell::Own && line[start + WIN_LENGTH] != Cell::Own;
+        if empty.len() == 1 && closed && own.count_ones() as usize == WIN_LENGTH - 1 {
+            found.push((own, empty[0]));
+        }
+    }
+    found
+}
+
+/// Counts the fours in a line, a four being marks that one more can turn into exactly five
+/// An open four like .XXXX. can be finished two ways but only counts once
+fn fours(line: &Cells) -> usize {
+    let mut masks = fives(line).into_iter().map(|(own, _)| own).collect::<Vec<_>>();
+    masks.sort_unstable();
+    masks.dedup();
+    masks.len()
+}
+
+/// Gets whether a line has an open four, which can be finished into five at either end
+fn is_open_four(line: &Cells) -> bool {
+    let found = fives(line);
+    found.iter().any(|(own, empty)| found.iter().any(|(other, other_empty)| own == other && empty != other_empty))
+}
+
+/// Gets whether a line has a three, marks that one more can turn into an open four
+/// This doesn't check whether the square that makes the open four would itself be forbidden
+fn is_three(line: &Cells) -> bool {
+    (0..line.len())
+        .filter(|i| line[*i] == Cell::Empty)
+        .any(|i| {
+            let mut next = *line;
+            next[i] = Cell::Own;
+            run_length(&next) <= WIN_LENGTH && is_open_four(&next)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sets up a Renju game with black playing crosses, taking turns so the given side is to move
+    /// White gets spare stones down the right edge if it needs more to keep the turns right
+    fn renju(black: &[(usize, usize)], white: &[(usize, usize)], to_move: Mark) -> Gomoku {
+        let white_count = if to_move == Mark::Cross {black.len()} else {black.len() - 1};
+        let white = (0..white_count)
+            .map(|i| white.get(i).copied().unwrap_or((SIZE - 1, 2 * i)))
+            .collect::<Vec<_>>();
+
+        let mut rules = Gomoku::new(GomokuRule::Renju);
+        for (i, (x, y)) in black.iter().enumerate() {
+            rules.apply_move(&Square::new(*x, *y), Mark::Cross);
+            if let Some((x, y)) = white.get(i) {
+                rules.apply_move(&Square::new(*x, *y), Mark::Nought);
+            }
+        }
+        rules
+    }
+
+    #[test]
+    fn double_three_is_forbidden() {
+        let rules = renju(&[(7, 5), (7, 6), (5, 7), (6, 7)], &[], Mark::Cross);
+        assert_eq!(rules.check_move(&Square::new(7, 7)), Err(MoveError::DoubleThree));
+        //a single three is fine
+        assert_eq!(rules.check_move(&Square::new(7, 4)), Ok(()));
+    }
+
+    #[test]
+    fn blocked_three_does_not_count() {
+        //white at the end of the vertical line means it can't become an open four
+        let rules = renju(&[(7, 5), (7, 6), (5, 7), (6, 7)], &[(7, 4), (7, 8)], Mark::Cross);
+        assert_eq!(rules.check_move(&Square::new(7, 7)), Ok(()));
+    }
+
+    #[test]
+    fn double_four_is_forbidden() {
+        let rules = renju(&[(4, 7), (5, 7), (6, 7), (7, 4), (7, 5), (7, 6)], &[], Mark::Cross);
+        assert_eq!(rules.check_move(&Square::new(7, 7)), Err(MoveError::DoubleFour));
+    }
+
+    #[test]
+    fn overline_is_forbidden() {
+        let rules = renju(&[(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)], &[], Mark::Cross);
+        assert_eq!(rules.check_move(&Square::new(5, 7)), Err(MoveError::Overline));
+    }
+
+    #[test]
+    fn exactly_five_is_always_allowed() {
+        //the move makes five across and a four down at the same time
+        let mut rules = renju(&[(3, 7), (4, 7), (5, 7), (6, 7), (7, 4), (7, 5), (7, 6)], &[], Mark::Cross);
+        assert_eq!(rules.check_move(&Square::new(7, 7)), Ok(()));
+
+        rules.apply_move(&Square::new(7, 7), Mark::Cross);
+        assert_eq!(rules.result().and_then(|result| result.winner()), Some(Mark::Cross));
+    }
+
+    #[test]
+    fn white_is_never_restricted() {
+        let black = [(0, 0), (0, 2), (0, 4), (0, 6), (0, 8), (0, 10)];
+        let rules = renju(&black, &[(7, 5), (7, 6), (5, 7), (6, 7), (2, 9)], Mark::Nought);
+        assert_eq!(rules.check_move(&Square::new(7, 7)), Ok(()));
+
+        //white wins with an overline
+        let mut rules = renju(&black, &[(2, 7), (3, 7), (4, 7), (6, 7), (7, 7)], Mark::Nought);
+        assert_eq!(rules.check_move(&Square::new(5, 7)), Ok(()));
+        rules.apply_move(&Square::new(5, 7), Mark::Nought);
+        assert_eq!(rules.result().and_then(|result| result.winner()), Some(Mark::Nought));
+    }
+}
@@ -0,0 +1,97 @@
+/// Gravity rules, like Connect Four
+/// A move only picks a column and the mark falls to the lowest empty square in it
+/// Rows go from the top down, so the lowest square in a column has the biggest y
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant};
+use crate::rules::Rules;
+
+/// Width of a Connect Four board
+pub const WIDTH: usize = 7;
+/// Height of a Connect Four board
+pub const HEIGHT: usize = 6;
+/// Number of marks in a row needed to win Connect Four
+pub const WIN_LENGTH: usize = 4;
+
+/// Rules for games where marks fall down columns
+#[derive(Debug, Clone)]
+pub struct Gravity {
+    board: Board,
+    result: Option<GameResult>,
+}
+
+impl Gravity {
+    /// Create the rules for a width by height board with win_length marks in a row needed to win
+    pub fn new(width: usize, height: usize, win_length: usize) -> Self {
+        Self {
+            board: Board::new(width, height, win_length),
+            result: None,
+        }
+    }
+}
+
+impl Default for Gravity {
+    /// Rules for a 7x6 Connect Four game
+    fn default() -> Self {
+        Self::new(WIDTH, HEIGHT, WIN_LENGTH)
+    }
+}
+
+impl Rules for Gravity {
+    fn variant(&self) -> Variant {
+        Variant::Gravity
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        (0..self.board.width())
+            .filter_map(|column| lowest_empty(&self.board, column))
+            .collect()
+    }
+
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        match self.board.get_square(square) {
+            None => return Err(MoveError::OutOfBounds),
+            Some(Mark::Empty) => (),
+            Some(_) => return Err(MoveError::Occupied),
+        }
+
+        //marks can't be left floating above an empty square
+        if lowest_empty(&self.board, square.x()) == Some(*square) {
+            Ok(())
+        } else {
+            Err(MoveError::NotLowest)
+        }
+    }
+
+    fn column_square(&self, column: usize) -> Result<Square, MoveError> {
+        if column >= self.board.width() {
+            return Err(MoveError::OutOfBounds);
+        }
+        lowest_empty(&self.board, column).ok_or(MoveError::ColumnFull)
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_square(square, mark);
+        self.result = self.board.game_over_at_square(square);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
+
+/// Gets the square a mark dropped in a column lands in, None if the column is full
+pub fn lowest_empty(board: &Board, column: usize) -> Option<Square> {
+    (0..board.height()).rev()
+        .find(|y| board.get_pos_coords(column, *y) == Some(Mark::Empty))
+        .map(|y| Square::new(column, y))
+}
@@ -1,2 +1,18 @@
+mod bitboard;
+pub mod clock;
 pub mod game;
-pub mod message;
\ No newline at end of file
+pub mod gomoku;
+pub mod gravity;
+pub mod mcts;
+pub mod message;
+pub mod misere;
+pub mod notation;
+pub mod order;
+pub mod quantum;
+pub mod qubic;
+pub mod record;
+pub mod rules;
+pub mod series;
+pub mod solver;
+pub mod ultimate;
+pub mod wild;
\ No newline at end of file
@@ -0,0 +1,237 @@
+/// Monte Carlo tree search player
+/// Uses UCT to pick moves, so it can play boards too big to solve
+use std::time::{Duration, Instant};
+
+use crate::game::{Board, Game, GameResult, Mark, Square};
+
+/// Exploration constant usually used for UCT
+pub const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// How much searching the player can do for a move
+/// There's always at least one iteration, so a game that isn't over always gets a move
+#[derive(Debug, Clone, Copy)]
+pub enum Budget {
+    Iterations(usize),
+    Time(Duration),
+}
+
+/// A node in the search tree
+/// Wins are counted from the side of the player that made the move into the node, draws are shared
+#[derive(Debug)]
+struct Node {
+    square: Option<Square>,
+    mover: Mark,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Square>,
+    result: Option<GameResult>,
+    visits: u32,
+    wins: f64,
+}
+
+impl Node {
+    /// Create a new node for the position after a move
+    fn new(square: Option<Square>, mover: Mark, parent: Option<usize>, board: &Board) -> Self {
+        let result = match square {
+            Some(square) => board.game_over_at_square(&square),
+            None => board.game_over(),
+        };
+        let untried = match result {
+            Some(_) => Vec::new(),
+            None => board.empty_squares(),
+        };
+        Self {
+            square,
+            mover,
+            parent,
+            children: Vec::new(),
+            untried,
+            result,
+            visits: 0,
+            wins: 0.0,
+        }
+    }
+}
+
+/// A player that picks moves using Monte Carlo tree search
+#[derive(Debug)]
+pub struct MctsPlayer {
+    budget: Budget,
+    exploration: f64,
+    rng: fastrand::Rng,
+}
+
+impl MctsPlayer {
+    /// Create a new player with a search budget, exploration constant and seed
+    /// The same seed and iteration budget will always pick the same moves
+    pub fn new(budget: Budget, exploration: f64, seed: u64) -> Self {
+        Self {
+            budget,
+            exploration,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    /// Picks a move for the current player of a game
+    /// Returns None if the game is already over
+    /// Only games played with the standard rules can be searched
+    pub fn choose_move<T: PartialEq + Copy>(&mut self, game: &Game<T>) -> Option<Square> {
+        if game.has_ended() {
+            return None;
+        }
+
+        //turn order starting from the current player
+        let ids = game.get_turn_order();
+        let curr = ids.iter().position(|id| *id == game.get_curr_player().id()).unwrap();
+        let order = (0..ids.len())
+            .map(|i| game.get_player_mark(ids[(curr + i) % ids.len()]).unwrap())
+            .collect::<Vec<_>>();
+
+        self.search_with_order(game.board(), &order)
+    }
+
+    /// Searches a two player board for the best move for a mark
+    pub fn search(&mut self, board: &Board, to_move: Mark) -> Option<Square> {
+        self.search_with_order(board, &[to_move, to_move.opponent()])
+    }
+
+    /// Searches a board for the best move for the first mark in a turn order
+    pub fn search_with_order(&mut self, board: &Board, order: &[Mark]) -> Option<Square> {
+        let last = order[order.len() - 1];
+        let mut tree = vec![Node::new(None, last, None, board)];
+        if tree[0].result.is_some() {
+            return None;
+        }
+
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let done = iterations > 0 && match self.budget {
+                Budget::Iterations(max) => iterations >= max,
+                Budget::Time(time) => start.elapsed() >= time,
+            };
+            if done {
+                break;
+            }
+            self.iterate(&mut tree, board, order);
+            iterations += 1;
+        }
+
+        //the most visited move is the most reliable
+        tree[0].children.iter()
+            .max_by_key(|child| tree[**child].visits)
+            .and_then(|child| tree[*child].square)
+    }
+
+    /// Does one round of selection, expansion, simulation and backpropagation
+    fn iterate(&mut self, tree: &mut Vec<Node>, root_board: &Board, order: &[Mark]) {
+        let mut board = root_board.clone();
+        let mut index = 0;
+
+        //select down the tree while every move of a node has been tried
+        while tree[index].untried.is_empty() && !tree[index].children.is_empty() {
+            index = self.select_child(tree, index);
+            let node = &tree[index];
+            let square = node.square.unwrap();
+            board.set_square(&square, node.mover);
+        }
+
+        //expand a random untried move
+        if !tree[index].untried.is_empty() {
+            let untried = &mut tree[index].untried;
+            let square = untried.swap_remove(self.rng.usize(0..untried.len()));
+            let mover = next_mark(order, tree[index].mover);
+            board.set_square(&square, mover);
+
+            let child = Node::new(Some(square), mover, Some(index), &board);
+            tree.push(child);
+            let child_index = tree.len() - 1;
+            tree[index].children.push(child_index);
+            index = child_index;
+        }
+
+        let result = match &tree[index].result {
+            Some(result) => result.clone(),
+            None => self.playout(&mut board, order, next_mark(order, tree[index].mover)),
+        };
+
+        //backpropagate the result up to the root
+        let mut current = Some(index);
+        while let Some(i) = current {
+            let node = &mut tree[i];
+            node.visits += 1;
+            node.wins += match result.winner() {
+                Some(mark) if mark == node.mover => 1.0,
+                Some(_) => 0.0,
+                None => 1.0 / order.len() as f64,
+            };
+            current = node.parent;
+        }
+    }
+
+    /// Picks the child of a node with the best upper confidence bound
+    fn select_child(&self, tree: &[Node], index: usize) -> usize {
+        let log_visits = (tree[index].visits as f64).ln();
+        let uct = |child: usize| {
+            let node = &tree[child];
+            let visits = node.visits as f64;
+            node.wins / visits + self.exploration * (log_visits / visits).sqrt()
+        };
+
+        let mut children = tree[index].children.iter();
+        let mut best = *children.next().unwrap();
+        let mut best_value = uct(best);
+        for child in children {
+            let value = uct(*child);
+            if value > best_value {
+                best = *child;
+                best_value = value;
+            }
+        }
+        best
+    }
+
+    /// Plays random moves on a board until the game ends
+    fn playout(&mut self, board: &mut Board, order: &[Mark], mut to_move: Mark) -> GameResult {
+        let mut squares = board.empty_squares();
+        loop {
+            let square = squares.swap_remove(self.rng.usize(0..squares.len()));
+            board.set_square(&square, to_move);
+            if let Some(result) = board.game_over_at_square(&square) {
+                return result;
+            }
+            to_move = next_mark(order, to_move);
+        }
+    }
+}
+
+/// Gets the mark that plays after a mark in a turn order
+fn next_mark(order: &[Mark], mark: Mark) -> Mark {
+    let index = order.iter().position(|other| *other == mark).unwrap();
+    order[(index + 1) % order.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_budgets_still_pick_a_move() {
+        let board = Board::new(3, 3, 3);
+        for budget in [Budget::Iterations(0), Budget::Time(Duration::from_secs(0))].iter() {
+            let mut player = MctsPlayer::new(*budget, DEFAULT_EXPLORATION, 3);
+            let square = player.search(&board, Mark::Cross).unwrap();
+            assert_eq!(board.get_square(&square), Some(Mark::Empty));
+        }
+    }
+
+    #[test]
+    fn finished_boards_have_no_move() {
+        let mut board = Board::new(3, 3, 3);
+        for x in 0..3 {
+            board.set_pos(x, 0, Mark::Cross);
+        }
+        let mut player = MctsPlayer::new(Budget::Iterations(100), DEFAULT_EXPLORATION, 3);
+        assert_eq!(player.search(&board, Mark::Nought), None);
+    }
+}
@@ -1,19 +1,139 @@
 /// Defines messages for sending and receiving to and from a user
 use serde::{Serialize, Deserialize};
 
-use crate::game::{Mark, Square};
+use crate::clock::ClockTime;
+use crate::game::{EndReason, Mark, MoveError, RequestError, Role, Square, Variant, WinningLine};
+use crate::quantum::QuantumMark;
+use crate::series::Score;
 
-/// Only message we receive is a move
+/// Messages received from players, they join the lobby for a variant then make moves
 #[derive(Serialize, Deserialize, Debug)]
 pub enum ReceiveMessage {
-    Move {pos: Square},
+    JoinLobby {variant: Variant},
+    //mark is the mark to place, only wild games let it be anything but the player's own
+    Move {
+        pos: Square,
+        #[serde(default)]
+        mark: Option<Mark>,
+    },
+    //a move that only picks a column, for variants with gravity
+    Drop {column: usize},
+    //picks which square a move that closed a cycle collapses into, for quantum games
+    Collapse {pos: Square},
+    Resign,
+    //offers a draw, or agrees to one if someone else already has
+    OfferDraw,
+    AcceptDraw,
+    //turns down a draw offer, or takes back the player's own
+    DeclineDraw,
+    //asks to play the same players again once a game is over, or agrees if someone else already has
+    RequestRematch,
+    AcceptRematch,
+    //offers to play a best of some number of games once a game is over, like best of 3
+    OfferMatch {games: usize},
+    AcceptMatch,
+    //leaves a finished game to wait for another of the same variant
+    ReturnToLobby,
+    //asks for the games that can be watched
+    ListGames,
+    //watches a game without playing in it, game is its number from the list of games
+    Spectate {game: usize},
+}
+
+impl ReceiveMessage {
+    /// Gets whether the message is something a player does in their game, which spectators can't do
+    pub fn is_game_action(&self) -> bool {
+        !matches!(self, ReceiveMessage::JoinLobby {..} | ReceiveMessage::ListGames | ReceiveMessage::Spectate {..})
+    }
+}
+
+/// A game going on that can be watched
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct LiveGame {
+    game: usize,
+    variant: Variant,
+}
+
+impl LiveGame {
+    /// Create a listing for a game with its number and variant
+    pub fn new(game: usize, variant: Variant) -> Self {
+        Self {game, variant}
+    }
+
+    /// Gets the number used to watch the game
+    pub fn game(&self) -> usize {
+        self.game
+    }
+
+    /// Gets which kind of game is being played
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
 }
 
 /// Different messages to send to players
 #[derive(Serialize, Deserialize, Debug)]
 pub enum SendMessage {
-    Move {mark: Mark, pos: Square},
-    StartGame {mark: Mark, first: bool},
-    GameOver {winner: bool, draw: bool},
+    //variants the server hosts, sent when a player connects
+    Variants {variants: Vec<Variant>},
+    //mark is who moved and piece is the mark they placed, these are only different in wild games
+    //clocks are the time everyone has left after the move, None if the game isn't timed
+    Move {mark: Mark, pos: Square, piece: Mark, clocks: Option<Vec<ClockTime>>},
+    StartGame {
+        mark: Mark,
+        first: bool,
+        variant: Variant,
+        width: usize,
+        height: usize,
+        //number of layers, 1 unless the board is 3D like Qubic
+        depth: usize,
+        win_length: usize,
+        order: Vec<Mark>,
+        //what the player is trying to do, for variants like Order and Chaos where players have different goals
+        role: Option<Role>,
+        //the time everyone starts with, None if the game isn't timed
+        clocks: Option<Vec<ClockTime>>,
+    },
+    //sub-boards the next move can be made in for ultimate games
+    Playable {boards: Vec<Square>},
+    //every mark in a quantum game after each move, with the squares to pick between if there's a collapse
+    //next is whoever picks the next square, since players can pick more than one in a row
+    Quantum {marks: Vec<QuantumMark>, collapse: Option<Vec<Square>>, next: Mark},
+    //line_lost is true when whoever made the line lost the game, like in misère games
+    //reason is how the game ended, like a line or someone resigning
+    //winners are the marks of everyone who won, for spectators who aren't playing
+    GameOver {winner: bool, draw: bool, line: Option<WinningLine>, line_lost: bool, reason: EndReason, winners: Vec<Mark>},
+    //marks of the players agreeing to a draw, the game is drawn once everyone has
+    DrawOffer {marks: Vec<Mark>},
+    //mark is who turned down the draw or took back their offer, also sent when a move turns one down
+    DrawDeclined {mark: Mark},
+    //marks of the players wanting a rematch, it starts once everyone does
+    RematchOffer {marks: Vec<Mark>},
+    //a match of some number of games that's been offered, with the marks of the players agreeing to it
+    MatchOffer {games: usize, marks: Vec<Mark>},
+    //the score of a match, sent when it starts and after each game
+    MatchScore {games: usize, played: usize, scores: Vec<Score>, draws: usize},
+    //sent once the last game of a match is over, draw is true when nobody won the most games
+    MatchOver {winner: bool, draw: bool},
+    //someone left the game, by disconnecting or going back to the lobby
     PlayerLeft,
+    //games that can be watched
+    Games {games: Vec<LiveGame>},
+    //the game being watched as it is now, sent when a spectator starts watching it
+    //board has the mark on every square in the same order as the squares are numbered
+    Spectating {
+        game: usize,
+        variant: Variant,
+        width: usize,
+        height: usize,
+        depth: usize,
+        win_length: usize,
+        order: Vec<Mark>,
+        board: Vec<Mark>,
+        next: Mark,
+        clocks: Option<Vec<ClockTime>>,
+    },
+    Error {error: MoveError},
+    //a request that isn't a move, like a draw offer or rematch, couldn't be made
+    Rejected {error: RequestError},
 }
\ No newline at end of file
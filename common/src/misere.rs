@@ -0,0 +1,203 @@
+/// Misère games, where making a line loses instead of winning
+/// Notakto is played on several 3x3 boards laid side by side, with every player placing crosses
+/// A board with a line on it is dead and can't be played on, and whoever kills the last board loses
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant, WinningLine};
+use crate::rules::Rules;
+use crate::ultimate::{self, SUB_SIZE};
+
+/// Number of boards in a usual game of Notakto
+pub const NOTAKTO_BOARDS: usize = 3;
+
+/// Rules for misère games, the board is the same as a standard game
+#[derive(Debug, Clone)]
+pub struct Misere {
+    board: Board,
+    result: Option<GameResult>,
+}
+
+impl Misere {
+    /// Create the rules for a width by height board where win_length marks in a row loses
+    pub fn new(width: usize, height: usize, win_length: usize) -> Self {
+        Self {
+            board: Board::new(width, height, win_length),
+            result: None,
+        }
+    }
+}
+
+impl Rules for Misere {
+    fn variant(&self) -> Variant {
+        Variant::Misere
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        match self.result {
+            Some(_) => Vec::new(),
+            None => self.board.empty_squares(),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_square(square, mark);
+        self.result = self.board.game_over_at_square(square).map(into_loss);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
+
+/// Rules for Notakto, the board is each of the 3x3 boards next to each other in a row
+/// Players keep their own marks so the loser is known, but only crosses go on the board
+#[derive(Debug, Clone)]
+pub struct Notakto {
+    board: Board,
+    result: Option<GameResult>,
+}
+
+impl Notakto {
+    /// Create the rules for a game with a number of boards
+    /// Panics if there are no boards
+    pub fn new(boards: usize) -> Self {
+        Self {
+            board: Board::new(boards * SUB_SIZE, SUB_SIZE, SUB_SIZE),
+            result: None,
+        }
+    }
+
+    /// Gets how many boards there are
+    pub fn boards(&self) -> usize {
+        self.board.width() / SUB_SIZE
+    }
+
+    /// Gets the boards that don't have a line on them yet, as coords in the row of boards
+    fn live_boards(&self) -> Vec<Square> {
+        (0..self.boards())
+            .map(|index| Square::new(index, 0))
+            .filter(|sub| ultimate::sub_result(&self.board, sub).and_then(|result| result.winner()).is_none())
+            .collect()
+    }
+}
+
+impl Default for Notakto {
+    /// Rules for a usual game with three boards
+    fn default() -> Self {
+        Self::new(NOTAKTO_BOARDS)
+    }
+}
+
+impl Rules for Notakto {
+    fn variant(&self) -> Variant {
+        Variant::Notakto
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        let live = self.live_boards();
+        self.board.empty_squares().into_iter()
+            .filter(|square| live.contains(&ultimate::sub_board_of(square)))
+            .collect()
+    }
+
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        match self.board.get_square(square) {
+            None => return Err(MoveError::OutOfBounds),
+            Some(Mark::Empty) => (),
+            Some(_) => return Err(MoveError::Occupied),
+        }
+
+        if self.live_boards().contains(&ultimate::sub_board_of(square)) {
+            Ok(())
+        } else {
+            Err(MoveError::WrongBoard)
+        }
+    }
+
+    fn check_piece(&self, mover: Mark, piece: Mark) -> Result<(), MoveError> {
+        //a move can say it places the cross that actually goes on the board, like in records
+        if piece == mover || piece == Mark::Cross {
+            Ok(())
+        } else {
+            Err(MoveError::WrongMark)
+        }
+    }
+
+    fn placed_piece(&self, _piece: Mark) -> Mark {
+        Mark::Cross
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_pos(square.x(), square.y(), Mark::Cross);
+
+        //only the board moved on can have died, and the game is over once they all have
+        let sub = ultimate::sub_board_of(square);
+        let small = ultimate::sub_board(&self.board, &sub);
+        let line = match small.game_over_at(square.x() % SUB_SIZE, square.y() % SUB_SIZE) {
+            Some(GameResult::Won {line, ..}) => line,
+            _ => return,
+        };
+        if self.live_boards().is_empty() {
+            let squares = line.squares().iter()
+                .map(|small_square| Square::new(sub.x() * SUB_SIZE + small_square.x(), small_square.y()))
+                .collect();
+            let line = WinningLine::new(squares, Some(*square));
+            self.result = Some(GameResult::Lost {loser: mark, line});
+        }
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn playable_boards(&self) -> Option<Vec<Square>> {
+        Some(self.live_boards())
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
+
+/// Turns a line that would have won into a loss for whoever made it
+fn into_loss(result: GameResult) -> GameResult {
+    match result {
+        GameResult::Won {winner, line} => GameResult::Lost {loser: winner, line},
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::{GameSettings, Mark, Player, Square, Variant};
+    use crate::record::GameRecord;
+
+    #[test]
+    fn notakto_moves_place_crosses() {
+        let mut game = GameSettings::for_variant(Variant::Notakto).new_game(vec![Player::new(Mark::Cross, 0), Player::new(Mark::Nought, 1)], 0);
+        game.make_move(&Square::new(0, 0));
+        game.make_move(&Square::new(4, 1));
+
+        let pieces = game.history().iter().map(|played| (played.mark(), played.piece())).collect::<Vec<_>>();
+        assert_eq!(pieces, vec![(Mark::Cross, Mark::Cross), (Mark::Nought, Mark::Cross)]);
+        assert_eq!(game.board().get_square(&Square::new(4, 1)), Some(Mark::Cross));
+
+        //the cross the second player placed is written down and can be read back
+        let replayed = GameRecord::from_game(&game).to_game(vec![0, 1]).unwrap();
+        assert_eq!(replayed.history().len(), 2);
+        assert_eq!(replayed.board().get_square(&Square::new(4, 1)), Some(Mark::Cross));
+    }
+}
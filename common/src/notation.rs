@@ -0,0 +1,321 @@
+/// Compact text notation for boards and positions
+/// A position is written as rows from top to bottom split by slashes, then the side to move,
+/// then the win length if it isn't the smaller of the width and height, like `x.o/.x./..o o`
+/// Marks for players after crosses and noughts are written as digits starting from 2
+/// Boards with layers write each layer from the top down split by bars, like `x.../..../..../....|...` for Qubic,
+/// and the default win length is then the smallest of the width, height and depth
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::game::{Board, Game, Mark, Square};
+use crate::rules::Rules;
+
+/// A board along with whose turn it is
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub board: Board,
+    pub to_move: Mark,
+}
+
+impl Position {
+    /// Create a new position
+    pub fn new(board: Board, to_move: Mark) -> Self {
+        Self {board, to_move}
+    }
+
+    /// Gets the position of a game
+    pub fn from_game<T: PartialEq + Copy, R: Rules + Clone>(game: &Game<T, R>) -> Self {
+        Self::new(game.board().clone(), game.get_curr_player().mark())
+    }
+}
+
+/// Reasons some notation couldn't be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Empty,
+    InvalidMark {row: usize, found: char},
+    RowLength {row: usize, expected: usize, found: usize},
+    LayerHeight {layer: usize, expected: usize, found: usize},
+    MissingSide,
+    InvalidSide(String),
+    InvalidWinLength(String),
+    TooManyFields,
+    ImpossibleCounts {counts: Vec<(Mark, usize)>, to_move: Mark},
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "No board given"),
+            ParseError::InvalidMark {row, found} => {
+                write!(f, "Row {} has '{}' but only 'x', 'o', '2' to '9' and '.' are allowed", row + 1, found)
+            },
+            ParseError::RowLength {row, expected, found} => {
+                write!(f, "Row {} has {} squares but the first row has {}", row + 1, found, expected)
+            },
+            ParseError::LayerHeight {layer, expected, found} => {
+                write!(f, "Layer {} has {} rows but the first layer has {}", layer + 1, found, expected)
+            },
+            ParseError::MissingSide => write!(f, "No side to move given after the board"),
+            ParseError::InvalidSide(side) => write!(f, "Side to move '{}' should be 'x', 'o' or '2' to '9'", side),
+            ParseError::InvalidWinLength(length) => {
+                write!(f, "Win length '{}' should be a number that fits on the board", length)
+            },
+            ParseError::TooManyFields => write!(f, "Too many fields, expected the board, side to move and win length"),
+            ParseError::ImpossibleCounts {counts, to_move} => {
+                let counts = counts.iter()
+                    .map(|(mark, count)| format!("{} '{}'", count, mark_char(*mark)))
+                    .collect::<Vec<_>>();
+                write!(f, "{} can't happen with '{}' to move", counts.join(", "), mark_char(*to_move))
+            },
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Gets the character used for a mark
+pub(crate) fn mark_char(mark: Mark) -> char {
+    match mark {
+        Mark::Cross => 'x',
+        Mark::Nought => 'o',
+        Mark::Other(other) => std::char::from_digit(other as u32 + 2, 10).unwrap_or('?'),
+        Mark::Empty => '.',
+    }
+}
+
+/// Gets the mark for a character, ignoring case
+pub(crate) fn char_mark(c: char) -> Option<Mark> {
+    match c.to_ascii_lowercase() {
+        'x' => Some(Mark::Cross),
+        'o' => Some(Mark::Nought),
+        '.' => Some(Mark::Empty),
+        '2'..='9' => Some(Mark::Other(c as u8 - b'2')),
+        _ => None,
+    }
+}
+
+/// Parses the rows of a board with a given win length, or the default one if None
+/// Rows are numbered from the top of the whole board, counting on through each layer
+fn parse_rows(rows: &str, win_length: Option<&str>) -> Result<Board, ParseError> {
+    let layers = rows.split('|')
+        .map(|layer| layer.split('/').collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let rows = layers.iter()
+        .flatten()
+        .enumerate()
+        .map(|(row, line)| {
+            line.chars()
+                .map(|c| char_mark(c).ok_or(ParseError::InvalidMark {row, found: c}))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let width = rows[0].len();
+    if width == 0 {
+        return Err(ParseError::Empty);
+    }
+    if let Some((row, marks)) = rows.iter().enumerate().find(|(_, marks)| marks.len() != width) {
+        return Err(ParseError::RowLength {row, expected: width, found: marks.len()});
+    }
+    let height = layers[0].len();
+    if let Some((layer, rows)) = layers.iter().enumerate().find(|(_, rows)| rows.len() != height) {
+        return Err(ParseError::LayerHeight {layer, expected: height, found: rows.len()});
+    }
+    let depth = layers.len();
+
+    let win_length = match win_length {
+        Some(length) => match length.parse::<usize>() {
+            Ok(value) if value > 0 && value <= width.max(height).max(depth) => value,
+            _ => return Err(ParseError::InvalidWinLength(length.to_string())),
+        },
+        None => default_win_length(width, height, depth),
+    };
+
+    let mut board = Board::new_3d(width, height, depth, win_length);
+    for (row, marks) in rows.iter().enumerate() {
+        for (x, mark) in marks.iter().enumerate() {
+            let square = match depth {
+                1 => Square::new(x, row),
+                _ => Square::new_3d(x, row % height, row / height),
+            };
+            board.set_square(&square, *mark);
+        }
+    }
+    Ok(board)
+}
+
+/// Gets the win length used when none is written
+fn default_win_length(width: usize, height: usize, depth: usize) -> usize {
+    match depth {
+        1 => width.min(height),
+        _ => width.min(height).min(depth),
+    }
+}
+
+impl fmt::Display for Board {
+    /// Writes the rows of the board, without the win length
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for z in 0..self.depth() {
+            if z > 0 {
+                write!(f, "|")?;
+            }
+            for y in 0..self.height() {
+                if y > 0 {
+                    write!(f, "/")?;
+                }
+                for x in 0..self.width() {
+                    let square = match self.depth() {
+                        1 => Square::new(x, y),
+                        _ => Square::new_3d(x, y, z),
+                    };
+                    write!(f, "{}", mark_char(self.get_square(&square).unwrap()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Board {
+    type Err = ParseError;
+
+    /// Reads the rows of a board, the win length is the smaller of the width and height, or the depth too
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_rows(s.trim(), None)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.board, mark_char(self.to_move))?;
+        let board = &self.board;
+        if board.win_length() != default_win_length(board.width(), board.height(), board.depth()) {
+            write!(f, " {}", self.board.win_length())?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Position {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let rows = fields.next().ok_or(ParseError::Empty)?;
+        let side = fields.next().ok_or(ParseError::MissingSide)?;
+        let win_length = fields.next();
+        if fields.next().is_some() {
+            return Err(ParseError::TooManyFields);
+        }
+
+        let board = parse_rows(rows, win_length)?;
+        let to_move = match char_mark(side.chars().next().unwrap()) {
+            Some(mark) if mark != Mark::Empty && side.len() == 1 => mark,
+            _ => return Err(ParseError::InvalidSide(side.to_string())),
+        };
+
+        //players take turns so the side to move can't be ahead of anyone, and can only be one behind
+        let cells = board.width() * board.height() * board.depth();
+        //there are always crosses and noughts even if they haven't moved yet
+        let mut counts: Vec<(Mark, usize)> = vec![(to_move, 0)];
+        for mark in [Mark::Cross, Mark::Nought].iter() {
+            if *mark != to_move {
+                counts.push((*mark, 0));
+            }
+        }
+        for mark in (0..cells).filter_map(|spot| board.get_pos(spot)).filter(|mark| *mark != Mark::Empty) {
+            match counts.iter_mut().find(|(counted, _)| *counted == mark) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((mark, 1)),
+            }
+        }
+        let mover = counts[0].1;
+        if counts.iter().any(|(_, count)| *count < mover || *count > mover + 1) {
+            counts.sort_by_key(|(mark, _)| mark.index());
+            return Err(ParseError::ImpossibleCounts {counts, to_move});
+        }
+
+        Ok(Position::new(board, to_move))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a position and writes it back, checking nothing changed either way
+    fn round_trip(text: &str) -> Position {
+        let position = text.parse::<Position>().unwrap();
+        assert_eq!(position.to_string(), text);
+
+        let again = position.to_string().parse::<Position>().unwrap();
+        assert_eq!(again.to_move, position.to_move);
+        assert_eq!(again.board.marks(), position.board.marks());
+        assert_eq!(again.board.win_length(), position.board.win_length());
+        position
+    }
+
+    #[test]
+    fn flat_positions_round_trip() {
+        let position = round_trip("x.o/.x./..o x");
+        assert_eq!(position.to_move, Mark::Cross);
+        assert_eq!(position.board.get_square(&Square::new(2, 0)), Some(Mark::Nought));
+
+        let position = round_trip("x..../...../..o../...../..... x 4");
+        assert_eq!((position.board.width(), position.board.win_length()), (5, 4));
+    }
+
+    #[test]
+    fn layered_positions_round_trip() {
+        let position = round_trip("x.../..../..../....|..../.o../..../....|..../..../..../....|..../..../..../.... x");
+        assert_eq!(position.board.depth(), 4);
+        assert_eq!(position.board.get_square(&Square::new_3d(1, 1, 1)), Some(Mark::Nought));
+    }
+
+    #[test]
+    fn multi_player_positions_round_trip() {
+        let position = round_trip("xo2/.3./... 4");
+        assert_eq!(position.to_move, Mark::Other(2));
+        assert_eq!(position.board.get_square(&Square::new(1, 1)), Some(Mark::Other(1)));
+    }
+
+    #[test]
+    fn bad_row_length() {
+        assert_eq!("x../../... o".parse::<Position>().unwrap_err(), ParseError::RowLength {row: 1, expected: 3, found: 2});
+        assert_eq!(
+            ".../.../...|.../... x".parse::<Position>().unwrap_err(),
+            ParseError::LayerHeight {layer: 1, expected: 3, found: 2},
+        );
+    }
+
+    #[test]
+    fn unknown_character() {
+        assert_eq!(".../.a./... x".parse::<Position>().unwrap_err(), ParseError::InvalidMark {row: 1, found: 'a'});
+    }
+
+    #[test]
+    fn bad_side_to_move() {
+        for side in ["q", ".", "xo"].iter() {
+            let text = format!(".../.../... {}", side);
+            assert_eq!(text.parse::<Position>().unwrap_err(), ParseError::InvalidSide(side.to_string()));
+        }
+        assert_eq!(".../.../...".parse::<Position>().unwrap_err(), ParseError::MissingSide);
+    }
+
+    #[test]
+    fn bad_win_length() {
+        for length in ["0", "4", "three"].iter() {
+            let text = format!(".../.../... x {}", length);
+            assert_eq!(text.parse::<Position>().unwrap_err(), ParseError::InvalidWinLength(length.to_string()));
+        }
+        assert_eq!(".../.../... x 3 3".parse::<Position>().unwrap_err(), ParseError::TooManyFields);
+    }
+
+    #[test]
+    fn impossible_counts() {
+        let error = "xx./.../... o".parse::<Position>().unwrap_err();
+        assert_eq!(error, ParseError::ImpossibleCounts {counts: vec![(Mark::Cross, 2), (Mark::Nought, 0)], to_move: Mark::Nought});
+    }
+}
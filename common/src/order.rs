@@ -0,0 +1,91 @@
+/// Order and Chaos, played on a 6x6 board where both players can place either mark
+/// Order tries to get exactly five of the same mark in a row, and Chaos wins if the board fills up without one
+/// Order always moves first, so the rules know which mark Order plays with from the first move
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant, WinningLine};
+use crate::gomoku::{self, DIRECTIONS};
+use crate::rules::Rules;
+
+/// Width and height of the board
+pub const SIZE: usize = 6;
+/// Number of the same mark in a row Order needs, six in a row doesn't count
+pub const WIN_LENGTH: usize = 5;
+
+/// Rules for Order and Chaos
+#[derive(Debug, Clone)]
+pub struct OrderChaos {
+    board: Board,
+    //the mark of the player playing Order, once they've moved
+    order: Option<Mark>,
+    result: Option<GameResult>,
+}
+
+impl OrderChaos {
+    /// Create the rules for an empty game
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(SIZE, SIZE, WIN_LENGTH),
+            order: None,
+            result: None,
+        }
+    }
+}
+
+impl Default for OrderChaos {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rules for OrderChaos {
+    fn variant(&self) -> Variant {
+        Variant::OrderChaos
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        match self.result {
+            Some(_) => Vec::new(),
+            None => self.board.empty_squares(),
+        }
+    }
+
+    fn check_piece(&self, _mover: Mark, piece: Mark) -> Result<(), MoveError> {
+        match piece {
+            Mark::Cross | Mark::Nought => Ok(()),
+            _ => Err(MoveError::WrongMark),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.apply_piece(square, mark, mark);
+    }
+
+    fn apply_piece(&mut self, square: &Square, mover: Mark, piece: Mark) {
+        self.board.set_square(square, piece);
+        let order = *self.order.get_or_insert(mover);
+
+        //a five counts for Order whoever finished it
+        let five = DIRECTIONS.iter()
+            .map(|direction| gomoku::row_through(&self.board, square, *direction, piece))
+            .find(|row| row.len() == WIN_LENGTH);
+        self.result = match five {
+            Some(row) => Some(GameResult::Won {winner: order, line: WinningLine::new(row, Some(*square))}),
+            //Chaos wins without a line
+            None if self.board.empty_squares().is_empty() => {
+                Some(GameResult::Won {winner: order.opponent(), line: WinningLine::new(Vec::new(), Some(*square))})
+            },
+            None => None,
+        };
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
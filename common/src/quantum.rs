@@ -0,0 +1,361 @@
+/// Quantum tictactoe, where each move puts spooky marks in two squares instead of one mark in one
+/// Spooky marks link their two squares in an entanglement graph, and a move that closes a cycle in it
+/// has to be collapsed, with the other player picking which of the move's two squares it ends up in
+/// Every spooky mark linked to that square is then forced into its other square, becoming a normal mark
+///
+/// A move is made as two squares one after the other by the same player, and a collapse is picked as a square,
+/// so a game is still just a list of squares
+/// If a collapse gives both players a line, the line finished by the earlier move wins
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant, WinningLine};
+use crate::rules::Rules;
+
+/// Width and height of the board, which is also how many in a row wins
+pub const SIZE: usize = 3;
+
+/// A mark placed by a move, which is spooky in two squares until it collapses into one of them
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct QuantumMark {
+    mark: Mark,
+    number: usize,
+    squares: Vec<Square>,
+    collapsed: bool,
+}
+
+impl QuantumMark {
+    /// Gets the mark of the player that placed it
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Gets which move placed the mark, starting from 1
+    pub fn number(&self) -> usize {
+        self.number
+    }
+
+    /// Gets the squares the mark is in, one once it's collapsed and two before
+    /// A move that hasn't had its second square picked yet also only has one
+    pub fn squares(&self) -> &[Square] {
+        &self.squares
+    }
+
+    /// Gets whether the mark has collapsed into a normal mark
+    pub fn is_collapsed(&self) -> bool {
+        self.collapsed
+    }
+
+    /// Gets whether the mark links two squares in the entanglement graph
+    fn is_spooky(&self) -> bool {
+        !self.collapsed && self.squares.len() == 2
+    }
+}
+
+/// What the next square picked does
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    //the first square of a move
+    First,
+    //the second square of a move, after the first
+    Second(Square),
+    //the square a mark collapses into, by its index in the marks
+    Collapse(usize),
+}
+
+/// Rules for quantum games, the board only has the marks that have collapsed
+#[derive(Debug, Clone)]
+pub struct Quantum {
+    board: Board,
+    marks: Vec<QuantumMark>,
+    phase: Phase,
+    //whether the player who picked the last square picks the next one too
+    keep_turn: bool,
+    result: Option<GameResult>,
+}
+
+impl Quantum {
+    /// Create the rules for an empty quantum game
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(SIZE, SIZE, SIZE),
+            marks: Vec::new(),
+            phase: Phase::First,
+            keep_turn: false,
+            result: None,
+        }
+    }
+
+    /// Gets whether two squares are linked by spooky marks
+    fn connected(&self, from: &Square, to: &Square) -> bool {
+        let mut seen = vec![*from];
+        let mut next = vec![*from];
+        while let Some(square) = next.pop() {
+            if square == *to {
+                return true;
+            }
+            let linked = self.marks.iter()
+                .filter(|mark| mark.is_spooky() && mark.squares.contains(&square))
+                .flat_map(|mark| mark.squares.iter().copied())
+                .collect::<Vec<_>>();
+            for other in linked {
+                if !seen.contains(&other) {
+                    seen.push(other);
+                    next.push(other);
+                }
+            }
+        }
+        false
+    }
+
+    /// Collapses a mark into a square, forcing every spooky mark linked to it into its other square
+    fn collapse(&mut self, index: usize, square: Square) {
+        let mut forced = vec![(index, square)];
+        while let Some((index, square)) = forced.pop() {
+            if self.marks[index].collapsed {
+                continue;
+            }
+            let placed = &mut self.marks[index];
+            placed.squares = vec![square];
+            placed.collapsed = true;
+            self.board.set_square(&square, placed.mark);
+
+            for (other, mark) in self.marks.iter().enumerate().filter(|(_, mark)| mark.is_spooky()) {
+                if let Some(position) = mark.squares.iter().position(|spooky| *spooky == square) {
+                    forced.push((other, mark.squares[1 - position]));
+                }
+            }
+        }
+    }
+
+    /// Gets the number of the move whose mark collapsed into a square
+    fn number_at(&self, square: &Square) -> usize {
+        self.marks.iter()
+            .find(|mark| mark.collapsed && mark.squares[0] == *square)
+            .map_or(0, |mark| mark.number)
+    }
+
+    /// Works out whether the game is over after marks have collapsed, the last square picked ending the line
+    fn find_result(&self, last_move: Square) -> Option<GameResult> {
+        let lines = (0..SIZE)
+            .map(|i| (0..SIZE).map(|j| Square::new(j, i)).collect::<Vec<_>>())
+            .chain((0..SIZE).map(|i| (0..SIZE).map(|j| Square::new(i, j)).collect()))
+            .chain(std::iter::once((0..SIZE).map(|i| Square::new(i, i)).collect()))
+            .chain(std::iter::once((0..SIZE).map(|i| Square::new(SIZE - 1 - i, i)).collect()));
+
+        //a line is finished by the latest move in it, and the earliest finished line wins
+        let winning = lines
+            .filter_map(|line| {
+                let mark = self.board.get_square(&line[0])?;
+                let same = line.iter().all(|square| self.board.get_square(square) == Some(mark));
+                if mark == Mark::Empty || !same {
+                    return None;
+                }
+                let finished = line.iter().map(|square| self.number_at(square)).max()?;
+                Some((finished, mark, line))
+            })
+            .min_by_key(|(finished, _, _)| *finished);
+
+        match winning {
+            Some((_, winner, line)) => Some(GameResult::Won {winner, line: WinningLine::new(line, Some(last_move))}),
+            None if self.board.empty_squares().is_empty() => Some(GameResult::Draw),
+            None => None,
+        }
+    }
+}
+
+impl Default for Quantum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rules for Quantum {
+    fn variant(&self) -> Variant {
+        Variant::Quantum
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        match self.phase {
+            Phase::First => self.board.empty_squares(),
+            Phase::Second(first) => self.board.empty_squares().into_iter().filter(|square| *square != first).collect(),
+            Phase::Collapse(index) => self.marks[index].squares.clone(),
+        }
+    }
+
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        let classical = match self.board.get_square(square) {
+            None => return Err(MoveError::OutOfBounds),
+            Some(mark) => mark != Mark::Empty,
+        };
+
+        match self.phase {
+            Phase::Collapse(index) if !self.marks[index].squares.contains(square) => Err(MoveError::WrongCollapse),
+            Phase::Collapse(_) => Ok(()),
+            _ if classical => Err(MoveError::Occupied),
+            Phase::Second(first) if first == *square => Err(MoveError::SameSquare),
+            _ => Ok(()),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        match self.phase {
+            //with one square left there's no room for a spooky mark so it's filled straight away
+            Phase::First if self.board.empty_squares().len() == 1 => {
+                let number = self.marks.len() + 1;
+                self.marks.push(QuantumMark {mark, number, squares: vec![*square], collapsed: true});
+                self.board.set_square(square, mark);
+                self.keep_turn = false;
+                self.result = self.find_result(*square);
+            },
+            Phase::First => {
+                let number = self.marks.len() + 1;
+                self.marks.push(QuantumMark {mark, number, squares: vec![*square], collapsed: false});
+                self.phase = Phase::Second(*square);
+                self.keep_turn = true;
+            },
+            Phase::Second(first) => {
+                //the squares being linked already would close a cycle
+                let cycle = self.connected(&first, square);
+                let index = self.marks.len() - 1;
+                self.marks[index].squares.push(*square);
+                self.phase = if cycle {Phase::Collapse(index)} else {Phase::First};
+                self.keep_turn = false;
+            },
+            //the player picking the collapse then makes their own move
+            Phase::Collapse(index) => {
+                self.collapse(index, *square);
+                self.phase = Phase::First;
+                self.keep_turn = true;
+                self.result = self.find_result(*square);
+            },
+        }
+    }
+
+    fn next_player(&self, current: usize, players: usize) -> usize {
+        if self.keep_turn {
+            current
+        } else {
+            (current + 1) % players
+        }
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn pending_collapse(&self) -> Option<Vec<Square>> {
+        match self.phase {
+            Phase::Collapse(index) if self.result.is_none() => Some(self.marks[index].squares.clone()),
+            _ => None,
+        }
+    }
+
+    fn quantum_marks(&self) -> Option<Vec<QuantumMark>> {
+        Some(self.marks.clone())
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameSettings, Player};
+
+    /// Picks squares one after the other, starting with crosses and taking turns the way the rules say
+    fn play(squares: &[(usize, usize)]) -> Quantum {
+        let mut rules = Quantum::new();
+        let mut player = 0;
+        for (x, y) in squares.iter() {
+            rules.apply_move(&Square::new(*x, *y), Mark::from_index(player));
+            player = rules.next_player(player, 2);
+        }
+        rules
+    }
+
+    #[test]
+    fn finds_cycles() {
+        let mut rules = play(&[(0, 0), (1, 0), (1, 0), (2, 0)]);
+        assert!(rules.connected(&Square::new(0, 0), &Square::new(2, 0)));
+        assert!(!rules.connected(&Square::new(0, 0), &Square::new(1, 1)));
+        assert_eq!(rules.pending_collapse(), None);
+
+        //linking the two ends of the chain closes a cycle
+        rules.apply_move(&Square::new(2, 0), Mark::Cross);
+        rules.apply_move(&Square::new(0, 0), Mark::Cross);
+        assert_eq!(rules.pending_collapse(), Some(vec![Square::new(2, 0), Square::new(0, 0)]));
+    }
+
+    #[test]
+    fn collapse_follows_chains() {
+        //a cycle through the top row with a nought hanging off the middle of it, then crosses collapse it
+        let rules = play(&[(0, 0), (1, 0), (1, 1), (1, 0), (1, 0), (2, 0), (2, 0), (0, 0), (0, 0)]);
+
+        let board = rules.board();
+        assert_eq!(board.get_square(&Square::new(0, 0)), Some(Mark::Nought));
+        assert_eq!(board.get_square(&Square::new(1, 0)), Some(Mark::Cross));
+        assert_eq!(board.get_square(&Square::new(2, 0)), Some(Mark::Cross));
+        assert_eq!(board.get_square(&Square::new(1, 1)), Some(Mark::Nought));
+        assert!(rules.quantum_marks().unwrap().iter().all(|mark| mark.is_collapsed()));
+    }
+
+    #[test]
+    fn collapse_can_only_pick_the_cycling_marks_squares() {
+        let rules = play(&[(0, 0), (1, 0), (1, 0), (0, 0)]);
+        assert_eq!(rules.legal_moves(), vec![Square::new(1, 0), Square::new(0, 0)]);
+        assert_eq!(rules.check_move(&Square::new(0, 0)), Ok(()));
+        assert_eq!(rules.check_move(&Square::new(2, 2)), Err(MoveError::WrongCollapse));
+    }
+
+    #[test]
+    fn other_player_collapses_then_moves() {
+        let mut game = GameSettings::for_variant(Variant::Quantum)
+            .new_game(vec![Player::new(Mark::Cross, 0), Player::new(Mark::Nought, 1)], 0);
+        for (x, y) in [(0, 0), (1, 0), (1, 0), (0, 0)].iter() {
+            game.make_move(&Square::new(*x, *y));
+        }
+        //noughts closed the cycle so crosses pick the collapse
+        assert!(game.pending_collapse().is_some());
+        assert_eq!(game.get_curr_player().mark(), Mark::Cross);
+        assert_eq!(game.can_collapse(&Square::new(0, 0), 1), Err(MoveError::NotYourTurn));
+
+        game.make_move(&Square::new(0, 0));
+        assert!(game.pending_collapse().is_none());
+        assert_eq!(game.get_curr_player().mark(), Mark::Cross);
+
+        //crosses then make both squares of their own move
+        game.make_move(&Square::new(2, 2));
+        assert_eq!(game.get_curr_player().mark(), Mark::Cross);
+        game.make_move(&Square::new(1, 1));
+        assert_eq!(game.get_curr_player().mark(), Mark::Nought);
+    }
+
+    #[test]
+    fn earlier_line_wins_when_both_finish_at_once() {
+        //each pair of moves makes a cycle in a column, crosses collapse them all so crosses take the top row
+        //the last collapse finishes both rows, and the crosses line was finished by an earlier move
+        let rules = play(&[(0, 0), (0, 1), (0, 1), (0, 0), (0, 1),
+            (1, 0), (1, 1), (1, 1), (1, 0), (1, 1),
+            (2, 0), (2, 1), (2, 1), (2, 0), (2, 1)]);
+
+        let board = rules.board();
+        assert!((0..SIZE).all(|x| board.get_square(&Square::new(x, 1)) == Some(Mark::Nought)));
+        match rules.result() {
+            Some(GameResult::Won {winner, line}) => {
+                assert_eq!(winner, Mark::Cross);
+                assert_eq!(line.squares(), &[Square::new(0, 0), Square::new(1, 0), Square::new(2, 0)][..]);
+            },
+            other => panic!("expected crosses to win, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+/// Qubic, tictactoe on a 4x4x4 cube
+/// The cube is four 4x4 layers stacked on top of each other, with lines along the layers, through them
+/// and across the space diagonals, 76 in total
+use crate::game::{Board, GameResult, Mark, Square, Variant};
+use crate::rules::Rules;
+
+/// Width, height and depth of the cube, which is also how many in a row wins
+pub const SIZE: usize = 4;
+
+/// Rules for Qubic
+#[derive(Debug, Clone)]
+pub struct Qubic {
+    board: Board,
+    result: Option<GameResult>,
+}
+
+impl Qubic {
+    /// Create the rules for an empty 4x4x4 game
+    pub fn new() -> Self {
+        Self {
+            board: Board::new_3d(SIZE, SIZE, SIZE, SIZE),
+            result: None,
+        }
+    }
+}
+
+impl Default for Qubic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rules for Qubic {
+    fn variant(&self) -> Variant {
+        Variant::Qubic
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        match self.result {
+            Some(_) => Vec::new(),
+            None => self.board.empty_squares(),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_square(square, mark);
+        self.result = self.board.game_over_at_square(square);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
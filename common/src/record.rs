@@ -0,0 +1,679 @@
+/// Text game records, a bit like chess PGN
+/// A record is a list of header tags then the moves and result, with a blank line after each game
+///
+/// [Cross "alice"]
+/// [Nought "bob"]
+/// [Date "2021.04.10"]
+/// [Variant "Standard"]
+/// [Size "3x3"]
+/// [WinLength "3"]
+/// [TimeControl "-"]
+/// [First "x"]
+/// [Result "1-0"]
+///
+/// b2 a1 c3 a3 a2 c1 c2 1-0
+///
+/// Squares are written as a column letter and a row number, so a1 is x = 0, y = 0
+/// Boards with layers have a size like 4x4x4 and squares have the layer after a colon, like b2:3
+/// A move that placed someone else's mark, like in wild games or Notakto, has the mark after an equals sign, like b2=o
+/// Players after crosses and noughts have tags Player3, Player4 and so on, and the result
+/// has a score for each player in mark order, like 0-0-1 or 1/3-1/3-1/3
+/// The time control is written like 300+2, see TimeControl::parse, or - for games without clocks
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::clock::TimeControl;
+use crate::game::{Game, GameResult, GameSettings, Mark, MoveError, Player, Square, Variant, MAX_PLAYERS, NUM_PLAYERS};
+use crate::notation::{char_mark, mark_char};
+use crate::rules::Rules;
+
+pub const CROSS_TAG: &str = "Cross";
+pub const NOUGHT_TAG: &str = "Nought";
+pub const DATE_TAG: &str = "Date";
+pub const VARIANT_TAG: &str = "Variant";
+pub const SIZE_TAG: &str = "Size";
+pub const WIN_LENGTH_TAG: &str = "WinLength";
+pub const TIME_CONTROL_TAG: &str = "TimeControl";
+pub const FIRST_TAG: &str = "First";
+pub const RESULT_TAG: &str = "Result";
+
+/// Result written for a game that hasn't finished
+pub const UNFINISHED: &str = "*";
+/// Most squares a recorded board can have, so reading a record can't make a huge board
+pub const MAX_SQUARES: usize = 10_000;
+/// Time control written for a game without clocks
+pub const NO_TIME_CONTROL: &str = "-";
+
+/// Maximum length of a line of moves when writing
+const LINE_LENGTH: usize = 80;
+
+/// A record of a game, with tags in the order they're written
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameRecord {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<RecordMove>,
+}
+
+/// A move in a record, the piece is only kept when the mover placed a mark that isn't their own
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordMove {
+    pub square: Square,
+    pub piece: Option<Mark>,
+}
+
+impl GameRecord {
+    /// Create an empty record
+    pub fn new() -> Self {
+        Self {
+            tags: Vec::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Makes a record of a game so far, using player ids for the player names
+    /// The date is taken from the first move, if there is one
+    pub fn from_game<T: PartialEq + Copy + Display, R: Rules + Clone>(game: &Game<T, R>) -> Self {
+        let mut record = Self::new();
+        let board = game.board();
+
+        let mut players = game.get_player_ids().into_iter()
+            .map(|id| (game.get_player_mark(id).unwrap(), id))
+            .collect::<Vec<_>>();
+        players.sort_by_key(|(mark, _)| mark.index());
+        for (mark, id) in players.iter() {
+            record.set_tag(&player_tag(*mark), &id.to_string());
+        }
+        if let Some(first) = game.history().first() {
+            record.set_tag(DATE_TAG, &format_date(first.time()));
+        }
+        record.set_tag(VARIANT_TAG, game.variant().name());
+        let size = match board.depth() {
+            1 => format!("{}x{}", board.width(), board.height()),
+            depth => format!("{}x{}x{}", board.width(), board.height(), depth),
+        };
+        record.set_tag(SIZE_TAG, &size);
+        record.set_tag(WIN_LENGTH_TAG, &board.win_length().to_string());
+        let time_control = game.time_control().map_or_else(|| NO_TIME_CONTROL.to_string(), |control| control.to_string());
+        record.set_tag(TIME_CONTROL_TAG, &time_control);
+
+        //the first mover is whoever made the first move, or is about to
+        let first = match game.history().first() {
+            Some(first) => first.mark(),
+            None => game.get_curr_player().mark(),
+        };
+        record.set_tag(FIRST_TAG, &mark_char(first).to_string());
+        record.set_tag(RESULT_TAG, &format_result(game.result(), players.len()));
+
+        record.moves = game.history().iter()
+            .map(|played| RecordMove {
+                square: played.square(),
+                piece: Some(played.piece()).filter(|piece| *piece != played.mark()),
+            })
+            .collect();
+        record
+    }
+
+    /// Replays the record as a game, the player ids are given in mark order starting with crosses
+    /// Every move is checked, so a record that doesn't follow the rules gives an error
+    pub fn to_game<T: PartialEq + Copy>(&self, ids: Vec<T>) -> Result<Game<T, Box<dyn Rules>>, RecordError> {
+        GameSettings::check_players(ids.len()).map_err(|_| RecordError::PlayerCount(ids.len()))?;
+        let variant = match self.tag(VARIANT_TAG) {
+            Some(name) => Variant::from_name(name)
+                .ok_or_else(|| RecordError::InvalidTag(VARIANT_TAG.to_string(), name.to_string()))?,
+            None => Variant::Standard,
+        };
+
+        let size = self.tag(SIZE_TAG).ok_or(RecordError::MissingTag(SIZE_TAG))?;
+        let (width, height, depth) = parse_size(size)
+            .ok_or_else(|| RecordError::InvalidTag(SIZE_TAG.to_string(), size.to_string()))?;
+
+        let win_length = match self.tag(WIN_LENGTH_TAG) {
+            Some(length) => length.parse::<usize>().ok()
+                .filter(|length| *length > 0 && *length <= width.max(height).max(depth))
+                .ok_or_else(|| RecordError::InvalidTag(WIN_LENGTH_TAG.to_string(), length.to_string()))?,
+            None if depth > 1 => width.min(height).min(depth),
+            None => width.min(height),
+        };
+
+        let time_control = match self.tag(TIME_CONTROL_TAG) {
+            None | Some(NO_TIME_CONTROL) => None,
+            Some(control) => Some(TimeControl::parse(control)
+                .ok_or_else(|| RecordError::InvalidTag(TIME_CONTROL_TAG.to_string(), control.to_string()))?),
+        };
+
+        let first = match self.tag(FIRST_TAG) {
+            None => 0,
+            Some(first) => first.chars().next()
+                .and_then(char_mark)
+                .and_then(|mark| mark.index())
+                .filter(|index| first.len() == 1 && *index < ids.len())
+                .ok_or_else(|| RecordError::InvalidTag(FIRST_TAG.to_string(), first.to_string()))?,
+        };
+
+        let players = ids.into_iter()
+            .enumerate()
+            .map(|(index, id)| Player::new(Mark::from_index(index), id))
+            .collect::<Vec<_>>();
+        let settings = GameSettings {variant, players: players.len(), width, height, depth, win_length, time_control};
+        let mut game = settings.new_game(players, first);
+        //variants like Qubic always have the same board, so the tags have to match it
+        let board = game.board();
+        if (board.width(), board.height(), board.depth()) != (width, height, depth) {
+            return Err(RecordError::InvalidTag(SIZE_TAG.to_string(), size.to_string()));
+        }
+        if let Some(length) = self.tag(WIN_LENGTH_TAG).filter(|_| board.win_length() != win_length) {
+            return Err(RecordError::InvalidTag(WIN_LENGTH_TAG.to_string(), length.to_string()));
+        }
+        for (number, recorded) in self.moves.iter().enumerate() {
+            let player = game.get_curr_player();
+            let (player_id, piece) = (player.id(), recorded.piece.unwrap_or_else(|| player.mark()));
+            //quantum games have collapses in among the moves
+            let checked = match game.pending_collapse() {
+                Some(_) => game.can_collapse(&recorded.square, player_id),
+                None => game.can_move_with(&recorded.square, player_id, piece),
+            };
+            checked.map_err(|error| RecordError::IllegalMove {number: number + 1, error})?;
+            game.make_move_with(&recorded.square, piece);
+        }
+
+        Ok(game)
+    }
+
+    /// Gets the value of a tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of a tag, adding it to the end if it isn't there already
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+}
+
+/// Reasons a record couldn't be read or replayed
+#[derive(Debug)]
+pub enum RecordError {
+    Io(io::Error),
+    InvalidTagLine(String),
+    InvalidMove(String),
+    MissingTag(&'static str),
+    InvalidTag(String, String),
+    PlayerCount(usize),
+    IllegalMove {number: usize, error: MoveError},
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordError::Io(error) => write!(f, "Couldn't read record: {}", error),
+            RecordError::InvalidTagLine(line) => write!(f, "Tag should look like [Name \"value\"], found {}", line),
+            RecordError::InvalidMove(token) => write!(f, "'{}' isn't a square like b2", token),
+            RecordError::MissingTag(tag) => write!(f, "Record has no {} tag", tag),
+            RecordError::InvalidTag(tag, value) => write!(f, "Invalid value '{}' for {} tag", value, tag),
+            RecordError::PlayerCount(players) => {
+                write!(f, "Records are played by {} to {} players, not {}", NUM_PLAYERS, MAX_PLAYERS, players)
+            },
+            RecordError::IllegalMove {number, error} => write!(f, "Move {} can't be played: {}", number, error),
+        }
+    }
+}
+
+impl Error for RecordError {}
+
+impl From<io::Error> for RecordError {
+    fn from(error: io::Error) -> Self {
+        RecordError::Io(error)
+    }
+}
+
+/// Reads a size like 3x3, or 4x4x4 for a board with layers
+/// Boards with more than MAX_SQUARES squares aren't allowed
+fn parse_size(size: &str) -> Option<(usize, usize, usize)> {
+    let values = size.split('x')
+        .map(|value| value.parse::<usize>().ok().filter(|value| *value > 0))
+        .collect::<Option<Vec<_>>>()?;
+    let (width, height, depth) = match values[..] {
+        [width, height] => (width, height, 1),
+        [width, height, depth] => (width, height, depth),
+        _ => return None,
+    };
+    let squares = width.checked_mul(height)?.checked_mul(depth)?;
+    Some((width, height, depth)).filter(|_| squares <= MAX_SQUARES)
+}
+
+/// Writes a square as a column letter and row number, with the layer after a colon if it has one
+/// Columns after z carry on as aa, ab and so on
+pub fn format_square(square: &Square) -> String {
+    let mut letters = Vec::new();
+    let mut column = square.x() + 1;
+    while column > 0 {
+        column -= 1;
+        letters.push((b'a' + (column % 26) as u8) as char);
+        column /= 26;
+    }
+    let name = letters.iter().rev().collect::<String>() + &(square.y() + 1).to_string();
+    match square.z() {
+        Some(z) => format!("{}:{}", name, z + 1),
+        None => name,
+    }
+}
+
+/// Reads a square written as a column letter and row number, with an optional layer after a colon
+pub fn parse_square(token: &str) -> Option<Square> {
+    let (token, layer) = match token.split_once(':') {
+        Some((token, layer)) => {
+            let layer = layer.parse::<usize>().ok().filter(|layer| *layer > 0)?;
+            (token, Some(layer))
+        },
+        None => (token, None),
+    };
+    let split = token.find(|c: char| !c.is_ascii_lowercase())?;
+    let (letters, digits) = token.split_at(split);
+    if letters.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    //a column too big to fit is as invalid as any other bad square
+    let column = letters.bytes().try_fold(0usize, |column, c| {
+        column.checked_mul(26)?.checked_add((c - b'a') as usize + 1)
+    })?;
+    let row = digits.parse::<usize>().ok().filter(|row| *row > 0)?;
+    Some(match layer {
+        Some(layer) => Square::new_3d(column - 1, row - 1, layer - 1),
+        None => Square::new(column - 1, row - 1),
+    })
+}
+
+/// Writes a move as its square, with the mark placed after an equals sign if it has one
+pub fn format_move(recorded: &RecordMove) -> String {
+    match recorded.piece {
+        Some(piece) => format!("{}={}", format_square(&recorded.square), mark_char(piece)),
+        None => format_square(&recorded.square),
+    }
+}
+
+/// Reads a move written as a square, with an optional mark after an equals sign
+pub fn parse_move(token: &str) -> Option<RecordMove> {
+    let (square, piece) = match token.split_once('=') {
+        Some((square, piece)) => {
+            let mut chars = piece.chars();
+            let piece = chars.next().and_then(char_mark).filter(|piece| *piece != Mark::Empty)?;
+            if chars.next().is_some() {
+                return None;
+            }
+            (square, Some(piece))
+        },
+        None => (token, None),
+    };
+    Some(RecordMove {square: parse_square(square)?, piece})
+}
+
+/// Gets the tag for the name of the player with a mark
+pub fn player_tag(mark: Mark) -> String {
+    match mark {
+        Mark::Cross => CROSS_TAG.to_string(),
+        Mark::Nought => NOUGHT_TAG.to_string(),
+        other => format!("Player{}", other.index().unwrap_or(0) + 1),
+    }
+}
+
+/// Writes a result as a score for each player in mark order, like 1-0 or 1/2-1/2
+pub fn format_result(result: Option<&GameResult>, players: usize) -> String {
+    let scores = match result {
+        Some(GameResult::Won {winner, ..}) => (0..players)
+            .map(|index| if Some(index) == winner.index() {"1".to_string()} else {"0".to_string()})
+            .collect::<Vec<_>>(),
+        //everyone but the player who made the line, ran out of time or resigned wins
+        Some(GameResult::Lost {loser, ..}) | Some(GameResult::OutOfTime {loser}) | Some(GameResult::Resigned {loser}) => (0..players)
+            .map(|index| if Some(index) == loser.index() {"0".to_string()} else {"1".to_string()})
+            .collect::<Vec<_>>(),
+        Some(GameResult::Draw) | Some(GameResult::DrawAgreed) => vec![format!("1/{}", players); players],
+        None => return UNFINISHED.to_string(),
+    };
+    scores.join("-")
+}
+
+/// Gets whether a token is a game result
+fn is_result(token: &str) -> bool {
+    if token == UNFINISHED {
+        return true;
+    }
+    let scores = token.split('-').collect::<Vec<_>>();
+    scores.len() >= 2 && scores.iter().all(|score| is_score(score))
+}
+
+/// Gets whether a token is a player's score, either a whole number or a fraction like 1/2
+fn is_score(score: &str) -> bool {
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|c| c.is_ascii_digit());
+    match score.split_once('/') {
+        Some((numerator, denominator)) => is_number(numerator) && is_number(denominator),
+        None => is_number(score),
+    }
+}
+
+/// Writes a time as a year.month.day date in UTC
+fn format_date(time: SystemTime) -> String {
+    let days = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs() / 86400).unwrap_or(0) as i64;
+
+    //convert days since 1970 to a civil date, from Howard Hinnant's date algorithms
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 {mp + 3} else {mp - 9};
+    let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Reads game records one at a time from a stream
+pub struct RecordReader<R> {
+    input: R,
+    pending: Option<String>,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// Create a new reader from a stream
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            pending: None,
+        }
+    }
+
+    /// Gets the next line, either one put back or from the stream
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        let mut line = String::new();
+        match self.input.read_line(&mut line)? {
+            0 => Ok(None),
+            _ => Ok(Some(line)),
+        }
+    }
+
+    /// Reads the next record, or None if the stream has ended
+    pub fn read_record(&mut self) -> Result<Option<GameRecord>, RecordError> {
+        let mut record = GameRecord::new();
+        let mut started = false;
+        let mut in_moves = false;
+
+        while let Some(line) = self.next_line()? {
+            let line = line.trim();
+            if line.is_empty() {
+                if in_moves {
+                    break;
+                }
+                continue;
+            }
+
+            if line.starts_with('[') {
+                if in_moves {
+                    //a new game started without a blank line
+                    self.pending = Some(line.to_string());
+                    break;
+                }
+                let (name, value) = parse_tag(line)?;
+                record.set_tag(&name, &value);
+                started = true;
+                continue;
+            }
+
+            in_moves = true;
+            started = true;
+            let mut finished = false;
+            for token in line.split_whitespace() {
+                if is_result(token) {
+                    finished = true;
+                    break;
+                }
+                //allow move numbers like 1. even though they aren't written
+                if token.ends_with('.') && token[..token.len() - 1].bytes().all(|c| c.is_ascii_digit()) {
+                    continue;
+                }
+                let recorded = parse_move(token).ok_or_else(|| RecordError::InvalidMove(token.to_string()))?;
+                record.moves.push(recorded);
+            }
+            if finished {
+                break;
+            }
+        }
+
+        Ok(if started {Some(record)} else {None})
+    }
+}
+
+impl<R: BufRead> Iterator for RecordReader<R> {
+    type Item = Result<GameRecord, RecordError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// Reads a tag line like [Name "value"]
+fn parse_tag(line: &str) -> Result<(String, String), RecordError> {
+    let invalid = || RecordError::InvalidTagLine(line.to_string());
+    let inner = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')).ok_or_else(invalid)?;
+    let (name, value) = inner.split_once(' ').ok_or_else(invalid)?;
+    let value = value.trim().strip_prefix('"').and_then(|value| value.strip_suffix('"')).ok_or_else(invalid)?;
+    if name.is_empty() {
+        return Err(invalid());
+    }
+    Ok((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+/// Writes game records one at a time to a stream
+pub struct RecordWriter<W> {
+    output: W,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Create a new writer to a stream
+    pub fn new(output: W) -> Self {
+        Self {output}
+    }
+
+    /// Writes a record followed by a blank line
+    pub fn write_record(&mut self, record: &GameRecord) -> io::Result<()> {
+        for (name, value) in record.tags.iter() {
+            writeln!(self.output, "[{} \"{}\"]", name, value.replace('"', "\\\""))?;
+        }
+        writeln!(self.output)?;
+
+        let result = record.tag(RESULT_TAG).filter(|result| is_result(result)).unwrap_or(UNFINISHED);
+        let tokens = record.moves.iter().map(format_move).chain(std::iter::once(result.to_string()));
+
+        let mut line_length = 0;
+        for token in tokens {
+            if line_length > 0 && line_length + 1 + token.len() > LINE_LENGTH {
+                writeln!(self.output)?;
+                line_length = 0;
+            }
+            if line_length > 0 {
+                write!(self.output, " ")?;
+                line_length += 1;
+            }
+            write!(self.output, "{}", token)?;
+            line_length += token.len();
+        }
+        writeln!(self.output)?;
+        writeln!(self.output)?;
+        self.output.flush()
+    }
+
+    /// Gets back the stream that was written to
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_game(variant: Variant) -> Game<usize, Box<dyn Rules>> {
+        GameSettings::for_variant(variant).new_game(vec![Player::new(Mark::Cross, 0), Player::new(Mark::Nought, 1)], 0)
+    }
+
+    /// Writes records to text and reads them back
+    fn write_and_read(records: &[GameRecord]) -> Vec<GameRecord> {
+        let mut writer = RecordWriter::new(Vec::new());
+        for record in records.iter() {
+            writer.write_record(record).unwrap();
+        }
+        let text = writer.into_inner();
+        RecordReader::new(&text[..]).collect::<Result<Vec<_>, _>>().unwrap()
+    }
+
+    /// Checks a game replayed from a record made the same moves as the original
+    fn assert_same_moves(original: &Game<usize, Box<dyn Rules>>, replayed: &Game<usize, Box<dyn Rules>>) {
+        let moves = |game: &Game<usize, Box<dyn Rules>>| game.history().iter()
+            .map(|played| (played.square(), played.mark(), played.piece()))
+            .collect::<Vec<_>>();
+        assert_eq!(moves(replayed), moves(original));
+        assert_eq!(replayed.board().marks(), original.board().marks());
+        assert_eq!(format_result(replayed.result(), 2), format_result(original.result(), 2));
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let mut standard = new_game(Variant::Standard);
+        for (x, y) in [(0, 0), (0, 1), (1, 1), (0, 2), (2, 2)].iter() {
+            standard.make_move(&Square::new(*x, *y));
+        }
+        assert!(standard.has_ended());
+
+        //both players place spooky marks in a1 and b1, which is a cycle that crosses collapse before moving again
+        let mut quantum = new_game(Variant::Quantum);
+        for (x, y) in [(0, 0), (1, 0), (0, 0), (1, 0)].iter() {
+            quantum.make_move(&Square::new(*x, *y));
+        }
+        assert!(quantum.pending_collapse().is_some());
+        quantum.make_move(&Square::new(0, 0));
+        quantum.make_move(&Square::new(2, 2));
+
+        //Order places noughts as well as crosses
+        let mut order = new_game(Variant::OrderChaos);
+        order.make_move_with(&Square::new(0, 0), Mark::Nought);
+        order.make_move_with(&Square::new(1, 1), Mark::Cross);
+        order.make_move_with(&Square::new(2, 2), Mark::Cross);
+
+        let games = [standard, quantum, order];
+        let records = games.iter().map(GameRecord::from_game).collect::<Vec<_>>();
+        let read = write_and_read(&records);
+        assert_eq!(read, records);
+
+        for (game, record) in games.iter().zip(read.iter()) {
+            assert_same_moves(game, &record.to_game(vec![0, 1]).unwrap());
+        }
+        assert_eq!(read[0].tag(RESULT_TAG), Some("1-0"));
+        assert_eq!(format_move(&read[2].moves[0]), "a1=o");
+        assert_eq!(read[1].moves[4].square, Square::new(0, 0));
+    }
+
+    #[test]
+    fn reads_records_without_blank_lines_between() {
+        let text = "[Size \"3x3\"]\n1. b2 a1 c3 *\n[Variant \"Qubic\"]\n[Size \"4x4x4\"]\n[WinLength \"4\"]\n\na1:1 b2:2\nc3:3 0-1\n";
+        let read = RecordReader::new(text.as_bytes()).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].moves.len(), 3);
+        assert_eq!(read[1].moves[2].square, Square::new_3d(2, 2, 2));
+
+        let game = read[1].to_game(vec![0, 1]).unwrap();
+        assert_eq!((game.board().depth(), game.history().len()), (4, 3));
+    }
+
+    #[test]
+    fn reader_errors() {
+        let read = |text: &str| RecordReader::new(text.as_bytes()).read_record();
+        assert!(matches!(read("[Size 3x3]\n"), Err(RecordError::InvalidTagLine(_))));
+        assert!(matches!(read("[ \"3x3\"]\n"), Err(RecordError::InvalidTagLine(_))));
+        assert!(matches!(read("b2 q0 *\n"), Err(RecordError::InvalidMove(token)) if token == "q0"));
+        assert!(matches!(read("b2=? *\n"), Err(RecordError::InvalidMove(_))));
+        assert!(matches!(read(""), Ok(None)));
+
+        let mut broken = RecordReader::new(&[b'[', 0xff, 0xfe, b'\n'][..]);
+        assert!(matches!(broken.read_record(), Err(RecordError::Io(_))));
+    }
+
+    #[test]
+    fn replay_errors() {
+        let replay = |text: &str| {
+            let record = RecordReader::new(text.as_bytes()).read_record().unwrap().unwrap();
+            record.to_game(vec![0, 1])
+        };
+        assert!(matches!(replay("b2 *\n"), Err(RecordError::MissingTag(SIZE_TAG))));
+        for (tag, value) in [(SIZE_TAG, "3y3"), (VARIANT_TAG, "Chess"), (WIN_LENGTH_TAG, "4"), (FIRST_TAG, "2"),
+            (TIME_CONTROL_TAG, "soon")].iter() {
+            let text = format!("[Size \"3x3\"]\n[{} \"{}\"]\n\nb2 *\n", tag, value);
+            assert!(matches!(replay(&text), Err(RecordError::InvalidTag(found, _)) if found == *tag));
+        }
+        assert!(matches!(
+            replay("[Size \"3x3\"]\n\nb2 a1 b2 *\n"),
+            Err(RecordError::IllegalMove {number: 3, error: MoveError::Occupied}),
+        ));
+        assert!(matches!(
+            replay("[Size \"3x3\"]\n\nb2=o *\n"),
+            Err(RecordError::IllegalMove {number: 1, error: MoveError::WrongMark}),
+        ));
+    }
+
+    #[test]
+    fn replay_checks_board_and_players() {
+        let replay = |text: &str, ids: Vec<usize>| {
+            let record = RecordReader::new(text.as_bytes()).read_record().unwrap().unwrap();
+            record.to_game(ids)
+        };
+        for size in ["100000x100000", "101x100", "99999999999x99999999999x99999", "3x3x0"].iter() {
+            let text = format!("[Size \"{}\"]\n\nb2 *\n", size);
+            assert!(matches!(replay(&text, vec![0, 1]), Err(RecordError::InvalidTag(tag, _)) if tag == SIZE_TAG));
+        }
+        assert!(matches!(
+            replay("[Variant \"Qubic\"]\n[Size \"3x3\"]\n\nb2 *\n", vec![0, 1]),
+            Err(RecordError::InvalidTag(tag, _)) if tag == SIZE_TAG,
+        ));
+        assert!(matches!(
+            replay("[Variant \"Gomoku\"]\n[Size \"15x15\"]\n[WinLength \"3\"]\n\nh8 *\n", vec![0, 1]),
+            Err(RecordError::InvalidTag(tag, _)) if tag == WIN_LENGTH_TAG,
+        ));
+        assert!(replay("[Variant \"Gomoku\"]\n[Size \"15x15\"]\n\nh8 *\n", vec![0, 1]).is_ok());
+        for ids in [vec![], vec![0], (0..11).collect()].iter() {
+            assert!(matches!(
+                replay("[Size \"3x3\"]\n\nb2 *\n", ids.clone()),
+                Err(RecordError::PlayerCount(players)) if players == ids.len(),
+            ));
+        }
+    }
+
+    #[test]
+    fn squares_round_trip() {
+        for square in [Square::new(0, 0), Square::new(25, 2), Square::new(26, 9), Square::new_3d(701, 0, 3)].iter() {
+            assert_eq!(parse_square(&format_square(square)), Some(*square));
+        }
+        assert_eq!(format_square(&Square::new(26, 0)), "aa1");
+        assert_eq!(format_square(&Square::new_3d(1, 1, 2)), "b2:3");
+    }
+
+    #[test]
+    fn huge_column_is_not_a_square() {
+        assert_eq!(parse_square("aaaaaaaaaaaaaaaaaaaa1"), None);
+        assert_eq!(parse_move("zzzzzzzzzzzzzzzzzzzz1=o"), None);
+
+        let text = "[Size \"3x3\"]\n\naaaaaaaaaaaaaaaaaaaa1 *\n";
+        let mut reader = RecordReader::new(text.as_bytes());
+        assert!(matches!(reader.read_record(), Err(RecordError::InvalidMove(_))));
+    }
+}
@@ -0,0 +1,207 @@
+/// Rules that decide how a game is played
+/// Each variant has its own rules which keep the board along with anything else they need to know
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant};
+use crate::quantum::QuantumMark;
+
+/// The rules of a variant, along with the state of a game played with them
+pub trait Rules: Send {
+    /// Gets which variant these are the rules for
+    fn variant(&self) -> Variant;
+
+    /// Gets the board the game is played on
+    fn board(&self) -> &Board;
+
+    /// Gets every square that can be moved in next
+    fn legal_moves(&self) -> Vec<Square>;
+
+    /// Checks whether a square can be moved in next, giving the reason if it can't
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        match self.board().get_square(square) {
+            None => Err(MoveError::OutOfBounds),
+            Some(Mark::Empty) => Ok(()),
+            Some(_) => Err(MoveError::Occupied),
+        }
+    }
+
+    /// Gets the square a move that only names a column would be made in
+    /// Only variants with gravity take moves like this
+    fn column_square(&self, _column: usize) -> Result<Square, MoveError> {
+        Err(MoveError::NeedsSquare)
+    }
+
+    /// Checks whether a player can place a mark, players can only place their own unless the variant lets them choose
+    fn check_piece(&self, mover: Mark, piece: Mark) -> Result<(), MoveError> {
+        if piece == mover {
+            Ok(())
+        } else {
+            Err(MoveError::WrongMark)
+        }
+    }
+
+    /// Gets the mark that goes on the board when a player places a piece
+    /// This is the piece itself unless the variant decides what's placed, like Notakto where it's always a cross
+    fn placed_piece(&self, piece: Mark) -> Mark {
+        piece
+    }
+
+    /// Makes a move with a mark, assumes check_move has been called
+    fn apply_move(&mut self, square: &Square, mark: Mark);
+
+    /// Makes a move where the mover placed a mark that might not be their own, assumes check_piece has been called
+    /// Only variants that let players choose their mark need to change this
+    fn apply_piece(&mut self, square: &Square, mover: Mark, _piece: Mark) {
+        self.apply_move(square, mover)
+    }
+
+    /// Gets the index of the player to move after the current one
+    fn next_player(&self, current: usize, players: usize) -> usize {
+        (current + 1) % players
+    }
+
+    /// Gets how the game finished, None if it hasn't
+    fn result(&self) -> Option<GameResult>;
+
+    /// Gets whether the game has finished
+    fn is_terminal(&self) -> bool {
+        self.result().is_some()
+    }
+
+    /// Gets the sub-boards the next move can be made in, None if the variant doesn't have sub-boards
+    fn playable_boards(&self) -> Option<Vec<Square>> {
+        None
+    }
+
+    /// Gets the squares the next player has to pick between to collapse an entanglement, None if there's nothing to collapse
+    fn pending_collapse(&self) -> Option<Vec<Square>> {
+        None
+    }
+
+    /// Gets every mark placed in a quantum game, None if the variant isn't quantum
+    fn quantum_marks(&self) -> Option<Vec<QuantumMark>> {
+        None
+    }
+
+    /// Copies the rules and their state into a box
+    fn boxed(&self) -> Box<dyn Rules>;
+}
+
+impl Clone for Box<dyn Rules> {
+    fn clone(&self) -> Self {
+        self.boxed()
+    }
+}
+
+impl Rules for Box<dyn Rules> {
+    fn variant(&self) -> Variant {
+        (**self).variant()
+    }
+
+    fn board(&self) -> &Board {
+        (**self).board()
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        (**self).legal_moves()
+    }
+
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        (**self).check_move(square)
+    }
+
+    fn column_square(&self, column: usize) -> Result<Square, MoveError> {
+        (**self).column_square(column)
+    }
+
+    fn check_piece(&self, mover: Mark, piece: Mark) -> Result<(), MoveError> {
+        (**self).check_piece(mover, piece)
+    }
+
+    fn placed_piece(&self, piece: Mark) -> Mark {
+        (**self).placed_piece(piece)
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        (**self).apply_move(square, mark)
+    }
+
+    fn apply_piece(&mut self, square: &Square, mover: Mark, piece: Mark) {
+        (**self).apply_piece(square, mover, piece)
+    }
+
+    fn next_player(&self, current: usize, players: usize) -> usize {
+        (**self).next_player(current, players)
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        (**self).result()
+    }
+
+    fn is_terminal(&self) -> bool {
+        (**self).is_terminal()
+    }
+
+    fn playable_boards(&self) -> Option<Vec<Square>> {
+        (**self).playable_boards()
+    }
+
+    fn pending_collapse(&self) -> Option<Vec<Square>> {
+        (**self).pending_collapse()
+    }
+
+    fn quantum_marks(&self) -> Option<Vec<QuantumMark>> {
+        (**self).quantum_marks()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        (**self).boxed()
+    }
+}
+
+/// Standard rules, where getting enough marks in a row on the board wins
+/// The default is a 3x3 game
+#[derive(Debug, Clone, Default)]
+pub struct Standard {
+    board: Board,
+    result: Option<GameResult>,
+}
+
+impl Standard {
+    /// Create the rules for a width by height board with win_length marks in a row needed to win
+    pub fn new(width: usize, height: usize, win_length: usize) -> Self {
+        Self {
+            board: Board::new(width, height, win_length),
+            result: None,
+        }
+    }
+}
+
+impl Rules for Standard {
+    fn variant(&self) -> Variant {
+        Variant::Standard
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        match self.result {
+            Some(_) => Vec::new(),
+            None => self.board.empty_squares(),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_square(square, mark);
+        //only need to look at lines through the move
+        self.result = self.board.game_over_at_square(square);
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,117 @@
+/// Matches, a series of games between the same players where whoever wins more than half of them wins the match
+/// Each game after the first is a rematch of the one before, so players take turns going first
+/// Drawn games don't count towards winning, so if nobody has won enough once every game is played
+/// whoever won the most games wins the match
+use serde::{Deserialize, Serialize};
+
+use crate::game::{Game, GameResult, GameSettings, Mark};
+use crate::rules::Rules;
+
+/// Numbers of games a match can be played over
+pub const MATCH_LENGTHS: [usize; 3] = [3, 5, 7];
+
+/// How many games a player has won in a match
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Score {
+    mark: Mark,
+    wins: usize,
+}
+
+impl Score {
+    /// Gets the mark of the player
+    pub fn mark(&self) -> Mark {
+        self.mark
+    }
+
+    /// Gets how many games the player has won
+    pub fn wins(&self) -> usize {
+        self.wins
+    }
+}
+
+/// A match being played, which keeps the score and makes each game after the one before
+#[derive(Debug, Clone)]
+pub struct Match {
+    settings: GameSettings,
+    length: usize,
+    scores: Vec<Score>,
+    draws: usize,
+    played: usize,
+}
+
+impl Match {
+    /// Create a match of some number of games between players with some marks
+    pub fn new(settings: GameSettings, length: usize, marks: Vec<Mark>) -> Self {
+        Self {
+            settings,
+            length,
+            scores: marks.into_iter().map(|mark| Score {mark, wins: 0}).collect(),
+            draws: 0,
+            played: 0,
+        }
+    }
+
+    /// Gets the settings every game in the match is played with
+    pub fn settings(&self) -> GameSettings {
+        self.settings
+    }
+
+    /// Gets how many games the match is played over
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Gets how many games each player has won
+    pub fn scores(&self) -> &[Score] {
+        &self.scores
+    }
+
+    /// Gets how many games were drawn
+    pub fn draws(&self) -> usize {
+        self.draws
+    }
+
+    /// Gets how many games have finished
+    pub fn played(&self) -> usize {
+        self.played
+    }
+
+    /// Adds the result of a finished game to the score
+    pub fn record(&mut self, result: &GameResult) {
+        self.played += 1;
+        if result.is_draw() {
+            self.draws += 1;
+            return;
+        }
+        for score in self.scores.iter_mut().filter(|score| result.won_by(score.mark)) {
+            score.wins += 1;
+        }
+    }
+
+    /// Gets whether the match is over, either by someone winning more than half the games or every game being played
+    pub fn is_over(&self) -> bool {
+        self.played >= self.length || self.scores.iter().any(|score| score.wins * 2 > self.length)
+    }
+
+    /// Gets the mark of the player who won the match, None if it isn't over or nobody won the most games
+    pub fn winner(&self) -> Option<Mark> {
+        if !self.is_over() {
+            return None;
+        }
+        let most = self.scores.iter().map(|score| score.wins).max()?;
+        let mut leaders = self.scores.iter().filter(|score| score.wins == most);
+        match (leaders.next(), leaders.next()) {
+            (Some(leader), None) => Some(leader.mark),
+            _ => None,
+        }
+    }
+
+    /// Makes the next game of the match as a rematch of the one before, None once the match is over
+    pub fn next_game<T: PartialEq + Copy, R: Rules + Clone>(&self, previous: &Game<T, R>) -> Option<Game<T, Box<dyn Rules>>> {
+        if self.is_over() {
+            return None;
+        }
+        Some(self.settings.rematch(previous))
+    }
+}
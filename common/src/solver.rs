@@ -0,0 +1,275 @@
+/// Perfect play solver for any board
+/// Uses negamax with alpha-beta pruning and a transposition table
+/// Only two player games between crosses and noughts can be solved
+use std::collections::HashMap;
+
+use crate::bitboard::Bitboard;
+use crate::game::{Board, Mark, Square};
+
+/// Score of a win found straight away, wins further away score lower
+const WIN_SCORE: i32 = 1_000_000;
+
+/// Game theoretic value of a position for the side to move
+/// Distances are the number of moves (plies) until the game ends with perfect play
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+    Win(usize),
+    Draw,
+    Loss(usize),
+}
+
+impl Value {
+    /// Gets the value from a score relative to the position
+    fn from_score(score: i32) -> Value {
+        if score > 0 {
+            Value::Win((WIN_SCORE - score) as usize)
+        } else if score < 0 {
+            Value::Loss((WIN_SCORE + score) as usize)
+        } else {
+            Value::Draw
+        }
+    }
+}
+
+/// The result of solving a position
+#[derive(Debug, Clone)]
+pub struct Solution {
+    /// Value of the position for the side to move
+    pub value: Value,
+    /// Every move that keeps the value, empty if the game is already over
+    pub moves: Vec<Square>,
+}
+
+/// Whether a stored score is exact or only a bound on the real score
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// An entry in the transposition table
+/// Scores are stored relative to the position they're for so they can be reused at any depth
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    score: i32,
+    bound: Bound,
+}
+
+/// Solves a board with perfect play given the side to move
+pub fn solve(board: &Board, to_move: Mark) -> Solution {
+    Solver::new().solve(board, to_move)
+}
+
+/// A solver, which keeps its transposition table between positions
+/// The table only holds positions for one board size and win length, and is cleared when these change
+#[derive(Debug, Default)]
+pub struct Solver {
+    table: HashMap<(Vec<Bitboard>, Mark), Entry>,
+    //width, height, depth and win length of the boards in the table
+    geometry: (usize, usize, usize, usize),
+}
+
+impl Solver {
+    /// Create a new solver with an empty transposition table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Solves a board with perfect play given the side to move
+    pub fn solve(&mut self, board: &Board, to_move: Mark) -> Solution {
+        //the same stones can be worth something else with another size or win length
+        let geometry = (board.width(), board.height(), board.depth(), board.win_length());
+        if geometry != self.geometry {
+            self.table.clear();
+            self.geometry = geometry;
+        }
+
+        if let Some(result) = board.game_over() {
+            let value = match result.winner() {
+                Some(mark) if mark == to_move => Value::Win(0),
+                Some(_) => Value::Loss(0),
+                None => Value::Draw,
+            };
+            return Solution {value, moves: Vec::new()};
+        }
+
+        let mut board = board.clone();
+        let mut best = -WIN_SCORE - 1;
+        let mut moves = Vec::new();
+
+        for square in ordered_moves(&board) {
+            //only need to know if a move is at least as good as the best so far
+            board.set_square(&square, to_move);
+            let score = match move_score(&board, &square, 0) {
+                Some(score) => score,
+                None => -self.negamax(&mut board, to_move.opponent(), 1, -WIN_SCORE - 1, -(best - 1)),
+            };
+            board.set_square(&square, Mark::Empty);
+
+            if score > best {
+                best = score;
+                moves.clear();
+            }
+            if score == best {
+                moves.push(square);
+            }
+        }
+
+        Solution {value: Value::from_score(best), moves}
+    }
+
+    /// Searches a position that isn't over yet returning the score for the side to move
+    /// Ply is how many moves deep from the root the position is
+    fn negamax(&mut self, board: &mut Board, to_move: Mark, ply: i32, mut alpha: i32, mut beta: i32) -> i32 {
+        let alpha_orig = alpha;
+        let key = (board.bitboards().to_vec(), to_move);
+        if let Some(entry) = self.table.get(&key) {
+            let score = from_table(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score),
+            }
+            if alpha >= beta {
+                return score;
+            }
+        }
+
+        let mut best = -WIN_SCORE - 1;
+        for square in ordered_moves(board) {
+            board.set_square(&square, to_move);
+            let score = match move_score(board, &square, ply) {
+                Some(score) => score,
+                None => -self.negamax(board, to_move.opponent(), ply + 1, -beta, -alpha),
+            };
+            board.set_square(&square, Mark::Empty);
+
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= alpha_orig {
+            Bound::Upper
+        } else if best >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(key, Entry {score: to_table(best, ply), bound});
+
+        best
+    }
+}
+
+/// Gets the score for the side that just moved if the move ended the game
+fn move_score(board: &Board, square: &Square, ply: i32) -> Option<i32> {
+    board.game_over_at_square(square).map(|result| match result.winner() {
+        Some(_) => WIN_SCORE - (ply + 1),
+        None => 0,
+    })
+}
+
+/// Gets the empty squares of a board, closest to the centre first since they're usually better
+fn ordered_moves(board: &Board) -> Vec<Square> {
+    let mut squares = board.empty_squares();
+    let centre_x = board.width() as isize - 1;
+    let centre_y = board.height() as isize - 1;
+    let centre_z = board.depth() as isize - 1;
+    squares.sort_by_key(|square| {
+        (2 * square.x() as isize - centre_x).abs()
+            + (2 * square.y() as isize - centre_y).abs()
+            + (2 * square.z().unwrap_or(0) as isize - centre_z).abs()
+    });
+    squares
+}
+
+/// Converts a score from being relative to the root to being relative to the position
+fn to_table(score: i32, ply: i32) -> i32 {
+    if score > 0 {
+        score + ply
+    } else if score < 0 {
+        score - ply
+    } else {
+        0
+    }
+}
+
+/// Converts a score from being relative to the position to being relative to the root
+fn from_table(score: i32, ply: i32) -> i32 {
+    if score > 0 {
+        score - ply
+    } else if score < 0 {
+        score + ply
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a 3x3 board with crosses and noughts on some squares
+    fn board(crosses: &[(usize, usize)], noughts: &[(usize, usize)]) -> Board {
+        let mut board = Board::new(3, 3, 3);
+        for (x, y) in crosses.iter() {
+            board.set_square(&Square::new(*x, *y), Mark::Cross);
+        }
+        for (x, y) in noughts.iter() {
+            board.set_square(&Square::new(*x, *y), Mark::Nought);
+        }
+        board
+    }
+
+    #[test]
+    fn empty_board_is_a_draw() {
+        let solution = solve(&board(&[], &[]), Mark::Cross);
+        assert_eq!(solution.value, Value::Draw);
+        assert_eq!(solution.moves.len(), 9);
+    }
+
+    #[test]
+    fn finds_win_in_one() {
+        let solution = solve(&board(&[(0, 0), (1, 0)], &[(0, 1), (1, 1)]), Mark::Cross);
+        assert_eq!(solution.value, Value::Win(1));
+        assert_eq!(solution.moves, vec![Square::new(2, 0)]);
+    }
+
+    #[test]
+    fn blocking_only_delays_a_loss() {
+        //noughts have to block the bottom row, then crosses make two lines at once
+        let solution = solve(&board(&[(0, 2), (2, 2)], &[(0, 1)]), Mark::Nought);
+        assert_eq!(solution.value, Value::Loss(4));
+        assert_eq!(solution.moves, vec![Square::new(1, 2)]);
+    }
+
+    #[test]
+    fn finished_board_has_no_moves() {
+        let solution = solve(&board(&[(0, 0), (1, 0), (2, 0)], &[(0, 1), (1, 1)]), Mark::Nought);
+        assert_eq!(solution.value, Value::Loss(0));
+        assert!(solution.moves.is_empty());
+    }
+
+    #[test]
+    fn reused_solver_keeps_win_lengths_apart() {
+        let mut three = Board::new(3, 3, 3);
+        three.set_square(&Square::new(0, 0), Mark::Cross);
+        let mut two = Board::new(3, 3, 2);
+        two.set_square(&Square::new(0, 0), Mark::Cross);
+
+        let mut solver = Solver::new();
+        assert_eq!(solver.solve(&three, Mark::Nought).value, Value::Draw);
+        assert_eq!(solver.solve(&two, Mark::Nought).value, Value::Loss(2));
+        assert_eq!(solver.solve(&three, Mark::Nought).value, Value::Draw);
+    }
+
+    #[test]
+    fn four_by_four_three_in_a_row_is_a_first_player_win() {
+        let solution = solve(&Board::new(4, 4, 3), Mark::Cross);
+        assert!(matches!(solution.value, Value::Win(_)));
+    }
+}
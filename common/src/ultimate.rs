@@ -0,0 +1,166 @@
+/// Ultimate tictactoe, played on nine 3x3 sub-boards laid out in a 3x3 grid
+/// The whole game is kept on one 9x9 board, so square x, y is in the sub-board at x / 3, y / 3
+/// Playing in a square sends the next player to the sub-board in the same place in the grid,
+/// unless that sub-board has already been won or filled, then they can play in any open sub-board
+/// Winning a sub-board claims it, and claiming three sub-boards in a row wins the game
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant, WinningLine};
+use crate::rules::Rules;
+
+/// Width and height of each sub-board, and how many sub-boards are in each row of the grid
+pub const SUB_SIZE: usize = 3;
+/// Width and height of the whole board
+pub const SIZE: usize = SUB_SIZE * SUB_SIZE;
+
+/// Rules for ultimate games
+#[derive(Debug, Clone)]
+pub struct Ultimate {
+    board: Board,
+    last_move: Option<Square>,
+    result: Option<GameResult>,
+}
+
+impl Ultimate {
+    /// Create the rules for an empty ultimate game
+    pub fn new() -> Self {
+        Self {
+            board: Board::new(SIZE, SIZE, SUB_SIZE),
+            last_move: None,
+            result: None,
+        }
+    }
+}
+
+impl Default for Ultimate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rules for Ultimate {
+    fn variant(&self) -> Variant {
+        Variant::Ultimate
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        if self.result.is_some() {
+            return Vec::new();
+        }
+        let playable = playable_boards(&self.board, self.last_move);
+        self.board.empty_squares().into_iter()
+            .filter(|square| playable.contains(&sub_board_of(square)))
+            .collect()
+    }
+
+    fn check_move(&self, square: &Square) -> Result<(), MoveError> {
+        match self.board.get_square(square) {
+            None => return Err(MoveError::OutOfBounds),
+            Some(Mark::Empty) => (),
+            Some(_) => return Err(MoveError::Occupied),
+        }
+
+        if playable_boards(&self.board, self.last_move).contains(&sub_board_of(square)) {
+            Ok(())
+        } else {
+            Err(MoveError::WrongBoard)
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.board.set_pos(square.x(), square.y(), mark);
+        self.last_move = Some(*square);
+        self.result = game_over_at(&self.board, square.x(), square.y());
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn playable_boards(&self) -> Option<Vec<Square>> {
+        Some(playable_boards(&self.board, self.last_move))
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
+
+/// Gets the sub-board a square is in, as coords in the grid of sub-boards
+pub fn sub_board_of(square: &Square) -> Square {
+    Square::new(square.x() / SUB_SIZE, square.y() / SUB_SIZE)
+}
+
+/// Gets a copy of one of the sub-boards on its own
+pub fn sub_board(board: &Board, sub: &Square) -> Board {
+    let mut small = Board::new(SUB_SIZE, SUB_SIZE, SUB_SIZE);
+    for y in 0..SUB_SIZE {
+        for x in 0..SUB_SIZE {
+            let mark = board.get_pos_coords(sub.x() * SUB_SIZE + x, sub.y() * SUB_SIZE + y).unwrap();
+            small.set_pos(x, y, mark);
+        }
+    }
+    small
+}
+
+/// Gets how a sub-board finished, None if it can still be played in
+pub fn sub_result(board: &Board, sub: &Square) -> Option<GameResult> {
+    sub_board(board, sub).game_over()
+}
+
+/// Gets the grid of sub-boards as a 3x3 board, with each won sub-board having the mark of its winner
+pub fn overall(board: &Board) -> Board {
+    let mut grid = Board::new(SUB_SIZE, SUB_SIZE, SUB_SIZE);
+    for sub in sub_boards() {
+        if let Some(winner) = sub_result(board, &sub).and_then(|result| result.winner()) {
+            grid.set_pos(sub.x(), sub.y(), winner);
+        }
+    }
+    grid
+}
+
+/// Gets the sub-boards the next move can be made in, given the last move made
+pub fn playable_boards(board: &Board, last_move: Option<Square>) -> Vec<Square> {
+    let open = sub_boards()
+        .filter(|sub| sub_result(board, sub).is_none())
+        .collect::<Vec<_>>();
+
+    //sent to the sub-board matching where the last move was made in its own sub-board
+    let target = last_move.map(|square| Square::new(square.x() % SUB_SIZE, square.y() % SUB_SIZE));
+    match target {
+        Some(target) if open.contains(&target) => vec![target],
+        _ => open,
+    }
+}
+
+/// Checks whether the game is over after a move
+/// A winning line goes through the middle squares of the sub-boards that won it
+/// Assumes the game wasn't over before the move
+pub fn game_over_at(board: &Board, x: usize, y: usize) -> Option<GameResult> {
+    let square = Square::new(x, y);
+    let sub = sub_board_of(&square);
+
+    let claimed = sub_result(board, &sub)?;
+    if claimed.winner().is_some() {
+        if let Some(GameResult::Won {winner, line}) = overall(board).game_over_at(sub.x(), sub.y()) {
+            let squares = line.squares().iter()
+                .map(|sub| Square::new(sub.x() * SUB_SIZE + SUB_SIZE / 2, sub.y() * SUB_SIZE + SUB_SIZE / 2))
+                .collect();
+            return Some(GameResult::Won {winner, line: WinningLine::new(squares, Some(square))});
+        }
+    }
+
+    //a draw once there's nowhere left to play
+    if sub_boards().all(|sub| sub_result(board, &sub).is_some()) {
+        Some(GameResult::Draw)
+    } else {
+        None
+    }
+}
+
+/// Iterates over the coords of every sub-board in the grid
+fn sub_boards() -> impl Iterator<Item = Square> {
+    (0..SUB_SIZE * SUB_SIZE).map(|index| Square::new(index % SUB_SIZE, index / SUB_SIZE))
+}
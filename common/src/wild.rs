@@ -0,0 +1,66 @@
+/// Wild tictactoe, where each move can place either a cross or a nought
+/// Whoever completes a line wins, whichever mark the line is made of
+use crate::game::{Board, GameResult, Mark, MoveError, Square, Variant};
+use crate::rules::Rules;
+
+/// Rules for wild games, the board is the same as a standard game
+#[derive(Debug, Clone)]
+pub struct Wild {
+    board: Board,
+    result: Option<GameResult>,
+}
+
+impl Wild {
+    /// Create the rules for a width by height board where win_length of either mark in a row wins
+    pub fn new(width: usize, height: usize, win_length: usize) -> Self {
+        Self {
+            board: Board::new(width, height, win_length),
+            result: None,
+        }
+    }
+}
+
+impl Rules for Wild {
+    fn variant(&self) -> Variant {
+        Variant::Wild
+    }
+
+    fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn legal_moves(&self) -> Vec<Square> {
+        match self.result {
+            Some(_) => Vec::new(),
+            None => self.board.empty_squares(),
+        }
+    }
+
+    fn check_piece(&self, _mover: Mark, piece: Mark) -> Result<(), MoveError> {
+        match piece {
+            Mark::Cross | Mark::Nought => Ok(()),
+            _ => Err(MoveError::WrongMark),
+        }
+    }
+
+    fn apply_move(&mut self, square: &Square, mark: Mark) {
+        self.apply_piece(square, mark, mark);
+    }
+
+    fn apply_piece(&mut self, square: &Square, mover: Mark, piece: Mark) {
+        self.board.set_square(square, piece);
+        //the line belongs to whoever finished it, not whoever's mark it's made of
+        self.result = self.board.game_over_at_square(square).map(|result| match result {
+            GameResult::Won {line, ..} => GameResult::Won {winner: mover, line},
+            other => other,
+        });
+    }
+
+    fn result(&self) -> Option<GameResult> {
+        self.result.clone()
+    }
+
+    fn boxed(&self) -> Box<dyn Rules> {
+        Box::new(self.clone())
+    }
+}
@@ -1,11 +1,21 @@
 use crate::server::Server;
 
+use common::game::GameSettings;
 use server::start_server;
 
 mod server;
 
 fn main() {
-    let server = Server::new();
+    let settings = match GameSettings::from_args(std::env::args().skip(1)) {
+        Ok(settings) => settings,
+        Err(error) => {
+            println!("{}", error);
+            println!("Usage: tictactoe-threads [players] [width] [height] [win length] or a variant name like ultimate, add time=300+2 for clocks");
+            return;
+        },
+    };
+
+    let server = Server::new(settings.hosted());
 
     start_server(server);
 }
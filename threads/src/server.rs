@@ -1,4 +1,4 @@
-use std::{collections::HashSet, io::ErrorKind, net::{TcpListener, TcpStream}};
+use std::{io::ErrorKind, net::{TcpListener, TcpStream}};
 use std::thread::spawn;
 use std::sync::Mutex;
 use std::sync::Arc;
@@ -10,57 +10,97 @@ use tungstenite::error::Error;
 use tungstenite::Message;
 use tungstenite::protocol::WebSocket;
 
-use common::{game::{Game, GameResult, Mark, NUM_PLAYERS, Player}, message::{ReceiveMessage, SendMessage}};
+use common::{game::{Game, GameResult, GameSettings, Mark, MoveError, Player, RequestError, Square, Variant}, message::{LiveGame, ReceiveMessage, SendMessage}, rules::Rules, series::Match};
 
 /// A server
 pub struct Server {
     websockets: HashMap<usize, WebSocket<TcpStream>>,
     counter: usize,
     messages: HashMap<usize, Vec<String>>,
-    lobby: HashSet<usize>,
-    games: Vec<Game<usize>>,
+    //map from id to the variant they're waiting to play
+    lobby: HashMap<usize, Variant>,
+    games: Vec<Game<usize, Box<dyn Rules>>>,
     //map from id to the game for efficiency
     game_map: HashMap<usize, usize>,
+    //settings for each variant hosted
+    settings: Vec<GameSettings>,
+    //map from the index of a game to the match it's part of
+    matches: HashMap<usize, Match>,
+    //map from the index of a game to the users watching it
+    spectators: HashMap<usize, Vec<usize>>,
+    //map from id to the index of the game they're watching
+    watching: HashMap<usize, usize>,
 }
 
 impl Server {
-    /// Creates a new server
-    pub fn new() -> Self {
+    /// Creates a new server that hosts a game for each of the given settings
+    pub fn new(settings: Vec<GameSettings>) -> Self {
         Self {
             websockets: HashMap::new(),
             counter: 0,
             messages: HashMap::new(),
-            lobby: HashSet::new(),
+            lobby: HashMap::new(),
             games: Vec::new(),
             game_map: HashMap::new(),
+            settings,
+            matches: HashMap::new(),
+            spectators: HashMap::new(),
+            watching: HashMap::new(),
         }
     }
 
     /// Disconnects a user from the server
     pub fn disconnect(&mut self, id: usize) {
-        let removed = self.lobby.remove(&id);
-        if !removed {
-            let game_index = *self.game_map.get(&id).unwrap();
-
-            //send to all other players in game that the player disconnected
-            println!("User {} left game", id);
-
-            let message_str = serde_json::to_string(&SendMessage::PlayerLeft).unwrap();
-            for player_id in self.games[game_index].get_player_ids().iter() {
-                if player_id != &id {
-                    if let Some(vec) = self.messages.get_mut(&player_id) {
-                        vec.push(message_str.clone());
-                    }
-                }
-            }
-            self.games[game_index].player_left();
-        } else {
+        if self.lobby.remove(&id).is_some() {
             println!("User {} removed from lobby", id);
+        } else if let Some(game_index) = self.game_map.get(&id).copied() {
+            println!("User {} left game", id);
+            self.leave_game(id, game_index);
         }
+        self.stop_watching(id);
 
         self.websockets.remove(&id);
         self.messages.remove(&id);
     }
+
+    /// Gets the settings games of a variant are played with, None if the server doesn't host it
+    pub fn variant_settings(&self, variant: Variant) -> Option<GameSettings> {
+        self.settings.iter().find(|settings| settings.variant == variant).copied()
+    }
+
+    /// Gets the users watching a game
+    pub fn game_spectators(&self, game_index: usize) -> Vec<usize> {
+        self.spectators.get(&game_index).cloned().unwrap_or_default()
+    }
+
+    /// Stops a user watching the game they're watching, if they are
+    pub fn stop_watching(&mut self, id: usize) {
+        if let Some(game_index) = self.watching.remove(&id) {
+            if let Some(spectators) = self.spectators.get_mut(&game_index) {
+                spectators.retain(|spectator| *spectator != id);
+            }
+        }
+    }
+
+    /// Takes a user out of a game, ending it if it's still going
+    pub fn leave_game(&mut self, id: usize, game_index: usize) {
+        self.game_map.remove(&id);
+
+        //send to the other players still in the game and anyone watching that the player left
+        let message_str = serde_json::to_string(&SendMessage::PlayerLeft).unwrap();
+        let still_in = self.games[game_index].get_player_ids().into_iter()
+            .filter(|player_id| self.game_map.get(player_id) == Some(&game_index))
+            .chain(self.game_spectators(game_index))
+            .collect::<Vec<_>>();
+        for player_id in still_in {
+            if let Some(vec) = self.messages.get_mut(&player_id) {
+                vec.push(message_str.clone());
+            }
+        }
+        self.games[game_index].player_left();
+        //a match can't go on without everyone
+        self.matches.remove(&game_index);
+    }
 }
 
 /// Starts a server
@@ -100,11 +140,11 @@ pub fn start_server(server: Server) {
         //add to hashmap
         server.websockets.insert(id, websocket);
         server.messages.insert(id, Vec::new());
-        server.lobby.insert(id);
 
-        if server.lobby.len() == NUM_PLAYERS {
-            start_game(&mut server);
-        } 
+        //let the user pick which variant to play
+        let variants = server.settings.iter().map(|settings| settings.variant).collect();
+        let message = SendMessage::Variants {variants};
+        send_one(&mut server, id, &serde_json::to_string(&message).unwrap());
         
         println!("user connected to server, id = {}", id);
 
@@ -120,6 +160,9 @@ fn setup_client(server_arc: Arc<Mutex<Server>>, id: usize) {
         
         let mut server = server_arc.lock().unwrap();
 
+        //the loop doesn't wait for messages, so clocks run out even when nobody sends anything
+        check_flag(&mut server, id);
+
         let mut messages = server.messages.get_mut(&id)
             .unwrap().iter()
             .map(|str| str.to_string()).collect::<Vec<_>>();
@@ -128,7 +171,7 @@ fn setup_client(server_arc: Arc<Mutex<Server>>, id: usize) {
 
         let websocket = server.websockets.get_mut(&id).unwrap();
         
-        while messages.len() > 0 {
+        while !messages.is_empty() {
             let _res = websocket.write_message(Message::Text(messages.remove(0)));
         }
 
@@ -168,124 +211,531 @@ fn setup_client(server_arc: Arc<Mutex<Server>>, id: usize) {
 }
 
 /// Handles a message from a user
-/// Far too nested, doesn't handle errors effectively
 fn handle_message(server: &mut Server, id: usize, msg: Message) {
-    match msg {
-        Message::Text(message) => {
-            match serde_json::from_str::<ReceiveMessage>(&message) {
-                Ok(message) => {
-                    match message {
-                        ReceiveMessage::Move { pos } => {
-                            let index_op = server.game_map.get(&id);
-                            match index_op {
-                                Some(index) => {
-                                    let index = *index;
-                                    let game = &mut server.games[index];
-                                    if game.can_move(&pos, id) {
-                                        let game_result = game.make_move(&pos);
-
-                                        let mark = game.get_player_mark(id).unwrap();
-                                        let message = SendMessage::Move{mark: mark, pos: pos};
-
-                                        let msg_str = serde_json::to_string(&message).unwrap();
-
-                                        //dispatch to all except one that made the move
-                                        send_all_but_one(server, id, &msg_str, Some(&index));
-
-                                        if game_result.is_some() {
-                                            match game_result.unwrap() {
-                                                GameResult::CrossWon => {
-                                                    for player_id in server.games[index].get_player_ids() {
-                                                        let won = Mark::Cross == server.games[index].get_player_mark(player_id).unwrap();
-                                                        let message = SendMessage::GameOver {winner: won, draw: false};
-                                                        let msg_str = serde_json::to_string(&message).unwrap();
-                                                        send_one(server, player_id, &msg_str);
-                                                    }
-                                                },
-                                                GameResult::NoughtWon => {
-                                                    for player_id in server.games[index].get_player_ids() {
-                                                        let won = Mark::Nought == server.games[index].get_player_mark(player_id).unwrap();
-                                                        let message = SendMessage::GameOver {winner: won, draw: false};
-                                                        let msg_str = serde_json::to_string(&message).unwrap();
-                                                        send_one(server, player_id, &msg_str);
-                                                    }
-                                                },
-                                                GameResult::Draw => {
-                                                    let message = SendMessage::GameOver{winner: false, draw: true};
-                                                    let msg_str = serde_json::to_string(&message).unwrap();
-                                                    send_all(server, &msg_str, Some(&index));
-                                                },
-                                            };
-
-                                            //end game here
-
-                                        }
-                                    }
-                                },
-                                None => {
-                                    //probably means the user isn't in a game
-                                }
-                            }
-                        },
-                    }
-                },
-                Err(_) => {
-                    println!("couldn't parse {:?}", message);
-                },
-            }
+    if let Message::Text(message) = msg {
+        match serde_json::from_str::<ReceiveMessage>(&message) {
+            Ok(message) => {
+                //spectators can only watch
+                if message.is_game_action() && server.watching.contains_key(&id) {
+                    send_rejected(server, id, RequestError::Spectating);
+                    return;
+                }
+
+                match message {
+                    ReceiveMessage::JoinLobby { variant } => {
+                        join_lobby(server, id, variant);
+                    },
+                    ReceiveMessage::Move { pos, mark } => {
+                        handle_move(server, id, pos, mark);
+                    },
+                    ReceiveMessage::Drop { column } => {
+                        handle_drop(server, id, column);
+                    },
+                    ReceiveMessage::Collapse { pos } => {
+                        handle_collapse(server, id, pos);
+                    },
+                    ReceiveMessage::Resign => {
+                        handle_resign(server, id);
+                    },
+                    ReceiveMessage::OfferDraw => {
+                        handle_draw_offer(server, id, false);
+                    },
+                    ReceiveMessage::AcceptDraw => {
+                        handle_draw_offer(server, id, true);
+                    },
+                    ReceiveMessage::DeclineDraw => {
+                        handle_decline_draw(server, id);
+                    },
+                    ReceiveMessage::RequestRematch => {
+                        handle_rematch(server, id, false);
+                    },
+                    ReceiveMessage::AcceptRematch => {
+                        handle_rematch(server, id, true);
+                    },
+                    ReceiveMessage::OfferMatch { games } => {
+                        handle_match_offer(server, id, Some(games));
+                    },
+                    ReceiveMessage::AcceptMatch => {
+                        handle_match_offer(server, id, None);
+                    },
+                    ReceiveMessage::ReturnToLobby => {
+                        return_to_lobby(server, id);
+                    },
+                    ReceiveMessage::ListGames => {
+                        list_games(server, id);
+                    },
+                    ReceiveMessage::Spectate { game } => {
+                        spectate(server, id, game);
+                    },
+                }
+            },
+            Err(_) => {
+                println!("couldn't parse {:?}", message);
+            },
+        }
+    }
+}
+
+/// Handles a move that only picks a column, finding the square it lands in
+fn handle_drop(server: &mut Server, id: usize, column: usize) {
+    let index = match move_game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    match server.games[index].column_square(column) {
+        Ok(pos) => handle_move(server, id, pos, None),
+        Err(error) => send_error(server, id, error),
+    }
+}
+
+/// Gets the index of the game a user is playing in, telling them if they aren't in one
+fn game_index(server: &mut Server, id: usize) -> Option<usize> {
+    let index = server.game_map.get(&id).copied();
+    if index.is_none() {
+        send_rejected(server, id, RequestError::NotInGame);
+    }
+    index
+}
+
+/// Gets the index of the game a user is playing in, telling them with a move error if they aren't in one
+fn move_game_index(server: &mut Server, id: usize) -> Option<usize> {
+    let index = server.game_map.get(&id).copied();
+    if index.is_none() {
+        send_error(server, id, MoveError::NotInGame);
+    }
+    index
+}
+
+/// Handles a move from a user, sending an error back if the move can't be made
+/// The mark placed is the player's own unless they chose one
+fn handle_move(server: &mut Server, id: usize, pos: Square, mark: Option<Mark>) {
+    let index = match move_game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        send_error(server, id, MoveError::GameOver);
+        return;
+    }
+
+    let game = &server.games[index];
+    let piece = mark.unwrap_or_else(|| game.get_curr_player().mark());
+    if let Err(error) = game.can_move_with(&pos, id, piece) {
+        send_error(server, id, error);
+        return;
+    }
+
+    play_move(server, index, id, pos, piece);
+}
+
+/// Handles a user picking where a quantum move collapses, sending an error back if it can't
+fn handle_collapse(server: &mut Server, id: usize, pos: Square) {
+    let index = match move_game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        send_error(server, id, MoveError::GameOver);
+        return;
+    }
+
+    let game = &server.games[index];
+    if let Err(error) = game.can_collapse(&pos, id) {
+        send_error(server, id, error);
+        return;
+    }
+
+    let piece = game.get_curr_player().mark();
+    play_move(server, index, id, pos, piece);
+}
+
+/// Handles a user resigning from their game
+fn handle_resign(server: &mut Server, id: usize) {
+    let index = match game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        send_rejected(server, id, RequestError::GameOver);
+        return;
+    }
+
+    match server.games[index].resign(id) {
+        Ok(result) => send_game_over(server, index, result),
+        Err(error) => send_rejected(server, id, error),
+    }
+}
+
+/// Handles a user offering a draw or accepting someone else's offer
+/// The game is drawn once everyone agrees, until then everyone is told who has
+fn handle_draw_offer(server: &mut Server, id: usize, accept: bool) {
+    let index = match game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        send_rejected(server, id, RequestError::GameOver);
+        return;
+    }
+
+    let game = &mut server.games[index];
+    let agreed = if accept {game.accept_draw(id)} else {game.offer_draw(id)};
+    match agreed {
+        Ok(Some(result)) => send_game_over(server, index, result),
+        Ok(None) => {
+            let message = SendMessage::DrawOffer {marks: server.games[index].draw_offer()};
+            send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
         },
-        _ => {},
+        Err(error) => send_rejected(server, id, error),
     }
 }
 
-/// Send a message to all users in a server or in a game in a server
-/// Option for the game index, if None send to all in server
-pub fn send_all(server: &mut Server, message: &String, game_index: Option<&usize>) {
-    match game_index {
-        Some(game_index) => {
-            for id in server.games[*game_index].get_player_ids().iter() {
-                let vec = server.messages.get_mut(id).unwrap();
-                vec.push(message.clone());
-            }
+/// Handles a user turning down a draw offer, telling everyone in the game
+fn handle_decline_draw(server: &mut Server, id: usize) {
+    let index = match game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        send_rejected(server, id, RequestError::GameOver);
+        return;
+    }
+
+    let game = &mut server.games[index];
+    match game.decline_draw(id) {
+        Ok(()) => {
+            let message = SendMessage::DrawDeclined {mark: game.get_player_mark(id).unwrap()};
+            send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
+        },
+        Err(error) => send_rejected(server, id, error),
+    }
+}
+
+/// Handles a user asking for a rematch or agreeing to one, starting it once everyone wants one
+fn handle_rematch(server: &mut Server, id: usize, accept: bool) {
+    let index = match game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    //the flag falling is news enough, and in a match the players may have moved on to the next game
+    if check_flag(server, id) {
+        return;
+    }
+
+    let game = &mut server.games[index];
+    let agreed = if accept {game.accept_rematch(id)} else {game.request_rematch(id)};
+    match agreed {
+        Ok(true) => start_rematch(server, index),
+        Ok(false) => {
+            let message = SendMessage::RematchOffer {marks: server.games[index].rematch_offer()};
+            send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
+        },
+        Err(error) => send_rejected(server, id, error),
+    }
+}
+
+/// Handles a user offering a match of some number of games, or agreeing to one when the number is None
+/// The match starts once everyone agrees
+fn handle_match_offer(server: &mut Server, id: usize, games: Option<usize>) {
+    let index = match game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        return;
+    }
+
+    let game = &mut server.games[index];
+    let agreed = match games {
+        Some(games) => game.offer_match(id, games),
+        None => game.accept_match(id),
+    };
+    match agreed {
+        Ok(Some(games)) => start_match(server, index, games),
+        Ok(None) => {
+            let (games, marks) = server.games[index].match_offer().unwrap();
+            let message = SendMessage::MatchOffer {games, marks};
+            send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
+        },
+        Err(error) => send_rejected(server, id, error),
+    }
+}
+
+/// Starts a match between the players of a finished game, its first game being a rematch
+fn start_match(server: &mut Server, index: usize, games: usize) {
+    let variant = server.games[index].variant();
+    let settings = match server.variant_settings(variant) {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let marks = server.games[index].get_player_ids().into_iter()
+        .map(|id| server.games[index].get_player_mark(id).unwrap())
+        .collect();
+    let series = Match::new(settings, games, marks);
+    if let Some(game) = series.next_game(&server.games[index]) {
+        begin_game(server, settings, game);
+        let next = server.games.len() - 1;
+        follow_game(server, index, next);
+        send_match_score(server, next, &series);
+        server.matches.insert(next, series);
+    }
+}
+
+/// Adds the result of a game to its match, if it's part of one, then starts the next game or ends the match
+fn continue_match(server: &mut Server, index: usize, result: &GameResult) {
+    let mut series = match server.matches.remove(&index) {
+        Some(series) => series,
+        None => return,
+    };
+    series.record(result);
+    send_match_score(server, index, &series);
+
+    match series.next_game(&server.games[index]) {
+        Some(game) => {
+            begin_game(server, series.settings(), game);
+            let next = server.games.len() - 1;
+            follow_game(server, index, next);
+            server.matches.insert(next, series);
         },
         None => {
-            for vec in server.messages.values_mut() {
-                vec.push(message.clone());
+            let winner = series.winner();
+            for player_id in server.games[index].get_player_ids() {
+                let won = winner.is_some() && winner == server.games[index].get_player_mark(player_id);
+                let message = SendMessage::MatchOver {winner: won, draw: winner.is_none()};
+                send_one(server, player_id, &serde_json::to_string(&message).unwrap());
             }
+        },
+    }
+}
+
+/// Tells the players of a game in a match what the score is
+fn send_match_score(server: &mut Server, index: usize, series: &Match) {
+    let message = SendMessage::MatchScore {
+        games: series.length(),
+        played: series.played(),
+        scores: series.scores().to_vec(),
+        draws: series.draws(),
+    };
+    send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
+}
+
+/// Sends a user the games going on that they can watch
+fn list_games(server: &mut Server, id: usize) {
+    let games = server.games.iter()
+        .enumerate()
+        .filter(|(_, game)| !game.has_ended())
+        .map(|(index, game)| LiveGame::new(index, game.variant()))
+        .collect();
+    let message = SendMessage::Games {games};
+    send_one(server, id, &serde_json::to_string(&message).unwrap());
+}
+
+/// Lets a user watch a game going on, sending them the game as it is now
+/// A user whose game is over leaves it first, and anyone waiting in the lobby stops waiting
+fn spectate(server: &mut Server, id: usize, index: usize) {
+    if let Some(playing) = server.game_map.get(&id).copied() {
+        if !server.games[playing].has_ended() {
+            send_rejected(server, id, RequestError::InGame);
+            return;
+        }
+        server.leave_game(id, playing);
+    }
+    if index >= server.games.len() || server.games[index].has_ended() {
+        send_rejected(server, id, RequestError::NoSuchGame);
+        return;
+    }
+
+    server.lobby.remove(&id);
+    server.stop_watching(id);
+    server.spectators.entry(index).or_default().push(id);
+    server.watching.insert(id, index);
+    println!("User {} watching game {}", id, index);
+    send_spectating(server, id, index);
+}
+
+/// Moves everyone watching a game on to the game after it, like the next game of a match
+fn follow_game(server: &mut Server, index: usize, next: usize) {
+    let spectators = server.spectators.remove(&index).unwrap_or_default();
+    for id in spectators.iter() {
+        server.watching.insert(*id, next);
+        send_spectating(server, *id, next);
+    }
+    server.spectators.insert(next, spectators);
+}
+
+/// Sends a spectator the game they're watching as it is now
+fn send_spectating(server: &mut Server, id: usize, index: usize) {
+    let game = &server.games[index];
+    let board = game.board();
+    let message = SendMessage::Spectating {
+        game: index,
+        variant: game.variant(),
+        width: board.width(),
+        height: board.height(),
+        depth: board.depth(),
+        win_length: board.win_length(),
+        order: game.get_turn_order().into_iter().map(|id| game.get_player_mark(id).unwrap()).collect(),
+        board: board.marks(),
+        next: game.get_curr_player().mark(),
+        clocks: game.clock_times(),
+    };
+
+    //some variants need more than the board to show the game
+    let quantum = game.quantum_marks().map(|marks| SendMessage::Quantum {
+        marks,
+        collapse: game.pending_collapse(),
+        next: game.get_curr_player().mark(),
+    });
+    let playable = game.playable_boards().map(|boards| SendMessage::Playable {boards});
+
+    for message in std::iter::once(message).chain(quantum).chain(playable) {
+        send_one(server, id, &serde_json::to_string(&message).unwrap());
+    }
+}
+
+/// Takes a user out of their finished game and puts them back in the lobby for the same variant
+fn return_to_lobby(server: &mut Server, id: usize) {
+    let index = match game_index(server, id) {
+        Some(index) => index,
+        None => return,
+    };
+
+    if check_flag(server, id) {
+        return;
+    }
+
+    if !server.games[index].has_ended() {
+        send_rejected(server, id, RequestError::GameNotOver);
+        return;
+    }
+    let variant = server.games[index].variant();
+    join_lobby(server, id, variant);
+}
+
+/// Makes a move that's already been checked, then tells the players in the game about it
+fn play_move(server: &mut Server, index: usize, id: usize, pos: Square, piece: Mark) {
+    let game = &mut server.games[index];
+    let offered = !game.draw_offer().is_empty();
+    let game_result = game.make_move_with(&pos, piece);
+    //the rules decide what actually went on the board, like a cross for everyone in Notakto
+    let piece = game.history().last().unwrap().piece();
+
+    let mark = game.get_player_mark(id).unwrap();
+    let message = SendMessage::Move{mark, pos, piece, clocks: game.clock_times()};
+
+    let msg_str = serde_json::to_string(&message).unwrap();
+
+    //dispatch to everyone, including who made the move so they know where it ended up
+    send_all(server, &msg_str, Some(&index));
+    send_quantum(server, index);
+
+    //moving turns down any draw offer the mover hadn't agreed to
+    if offered && game_result.is_none() && server.games[index].draw_offer().is_empty() {
+        let message = SendMessage::DrawDeclined {mark};
+        send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
+    }
+
+    match game_result {
+        Some(result) => send_game_over(server, index, result),
+        None => send_playable(server, index),
+    }
+}
+
+/// Ends the game a user is in if the player to move has run out of time, telling the players
+/// Returns whether their time ran out
+fn check_flag(server: &mut Server, id: usize) -> bool {
+    let index = match server.game_map.get(&id) {
+        Some(index) => *index,
+        None => return false,
+    };
+
+    match server.games[index].check_flag() {
+        Some(result) => {
+            send_game_over(server, index, result);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Tells the players of a game how it ended, then carries on with its match if it's part of one
+fn send_game_over(server: &mut Server, index: usize, result: GameResult) {
+    let line = result.line().cloned();
+    let line_lost = result.is_line_lost();
+    let reason = result.reason();
+    let winners = server.games[index].get_player_ids().into_iter()
+        .map(|player_id| server.games[index].get_player_mark(player_id).unwrap())
+        .filter(|mark| result.won_by(*mark))
+        .collect::<Vec<_>>();
+    if result.is_draw() {
+        let message = SendMessage::GameOver{winner: false, draw: true, line, line_lost, reason, winners};
+        let msg_str = serde_json::to_string(&message).unwrap();
+        send_all(server, &msg_str, Some(&index));
+    } else {
+        for player_id in server.games[index].get_player_ids() {
+            let won = result.won_by(server.games[index].get_player_mark(player_id).unwrap());
+            let message = SendMessage::GameOver {winner: won, draw: false, line: line.clone(), line_lost, reason, winners: winners.clone()};
+            let msg_str = serde_json::to_string(&message).unwrap();
+            send_one(server, player_id, &msg_str);
+        }
+        let message = SendMessage::GameOver {winner: false, draw: false, line, line_lost, reason, winners};
+        let msg_str = serde_json::to_string(&message).unwrap();
+        for spectator in server.game_spectators(index) {
+            send_one(server, spectator, &msg_str);
         }
     }
+    continue_match(server, index, &result);
 }
 
-/// Send a message to all users in a server/game except one
-/// Option for the game index, if None send to all in server(but one)
-pub fn send_all_but_one(server: &mut Server, id: usize, message: &String, game_index: Option<&usize>) {
+/// Send a message to all users in a server or in a game in a server
+/// Option for the game index, if None send to all in server
+/// Anyone watching the game gets the message too
+pub fn send_all(server: &mut Server, message: &str, game_index: Option<&usize>) {
     match game_index {
         Some(game_index) => {
-            for player_id in server.games[*game_index].get_player_ids().iter() {
-                if player_id != &id {
-                    let vec = server.messages.get_mut(player_id).unwrap();
-                    vec.push(message.clone());
-                }    
+            let spectators = server.game_spectators(*game_index);
+            for id in server.games[*game_index].get_player_ids().iter().chain(spectators.iter()) {
+                let vec = server.messages.get_mut(id).unwrap();
+                vec.push(message.to_string());
             }
         },
         None => {
-            for (player_id, vec) in server.messages.iter_mut() {
-                if player_id != &id {
-                    vec.push(message.clone());
-                }
+            for vec in server.messages.values_mut() {
+                vec.push(message.to_string());
             }
         }
     }
 }
 
+/// Send an error to a user whose move couldn't be made
+pub fn send_error(server: &mut Server, id: usize, error: MoveError) {
+    let message = SendMessage::Error {error};
+    send_one(server, id, &serde_json::to_string(&message).unwrap());
+}
+
+/// Send an error to a user whose request, like a draw offer or rematch, couldn't be made
+pub fn send_rejected(server: &mut Server, id: usize, error: RequestError) {
+    let message = SendMessage::Rejected {error};
+    send_one(server, id, &serde_json::to_string(&message).unwrap());
+}
+
 /// Send a message to a single user in a server
-pub fn send_one(server: &mut Server, id: usize, message: &String) {
+pub fn send_one(server: &mut Server, id: usize, message: &str) {
     //println!("Sending {} to {}", message, id);
     let vec = server.messages.get_mut(&id);
     
     match vec {
         Some(vec) => {
-            vec.push(message.clone());
+            vec.push(message.to_string());
         },
         None => {
             println!("Invalid id - {} for message {}", id, message);
@@ -293,34 +743,114 @@ pub fn send_one(server: &mut Server, id: usize, message: &String) {
     }
 }
 
-/// Starts a game
-fn start_game(server: &mut Server) {
-    let mut persons = Vec::new();
+/// Puts a user in the lobby for a variant, starting a game once enough are waiting
+/// A user whose game is over leaves it first
+fn join_lobby(server: &mut Server, id: usize, variant: Variant) {
+    if let Some(index) = server.game_map.get(&id).copied() {
+        if !server.games[index].has_ended() {
+            println!("User {} tried to join the lobby from a game", id);
+            return;
+        }
+        server.leave_game(id, index);
+    }
+    server.stop_watching(id);
+    let settings = match server.variant_settings(variant) {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    server.lobby.insert(id, variant);
+    println!("User {} waiting for a {} game", id, variant.name());
+
+    let waiting = server.lobby.values().filter(|waiting| **waiting == variant).count();
+    if waiting >= settings.players {
+        start_game(server, settings);
+    }
+}
 
-    let first = fastrand::usize(0..NUM_PLAYERS);
-    for (i, id) in server.lobby.iter().enumerate() {
-        let messages = server.messages.get_mut(&id).unwrap();
+/// Starts a game given enough players have joined the lobby for its variant
+fn start_game(server: &mut Server, settings: GameSettings) {
+    let ids = server.lobby.iter()
+        .filter(|(_, variant)| **variant == settings.variant)
+        .map(|(id, _)| *id)
+        .take(settings.players)
+        .collect::<Vec<_>>();
+
+    let first = fastrand::usize(0..ids.len());
+    let persons = ids.iter()
+        .enumerate()
+        .map(|(i, id)| Player::new(Mark::from_index(i), *id))
+        .collect::<Vec<_>>();
+
+    for id in ids.iter() {
+        server.lobby.remove(id);
+    }
 
-        let (mark, first) = match i % 2 == 0 {
-            true => (Mark::Cross, i == first),
-            false => (Mark::Nought, i == first),
-        };
+    let game = settings.new_game(persons, first);
+    begin_game(server, settings, game);
+}
 
-        let player = Player::new(mark, *id);
-        persons.push(player);
+/// Starts a rematch of a finished game once all its players want one
+fn start_rematch(server: &mut Server, index: usize) {
+    let variant = server.games[index].variant();
+    let settings = match server.variant_settings(variant) {
+        Some(settings) => settings,
+        None => return,
+    };
+
+    let game = settings.rematch(&server.games[index]);
+    begin_game(server, settings, game);
+    follow_game(server, index, server.games.len() - 1);
+}
 
-        let start_mess = SendMessage::StartGame {mark, first};
+/// Tells the players of a new game that it's starting and adds it to the server
+fn begin_game(server: &mut Server, settings: GameSettings, game: Game<usize, Box<dyn Rules>>) {
+    let first = game.get_curr_player().id();
+
+    //marks in the order players take turns
+    let order = game.get_turn_order().into_iter()
+        .map(|id| game.get_player_mark(id).unwrap())
+        .collect::<Vec<_>>();
+
+    for id in game.get_player_ids() {
+        let start_mess = SendMessage::StartGame {
+            mark: game.get_player_mark(id).unwrap(),
+            first: id == first,
+            variant: settings.variant,
+            width: settings.width,
+            height: settings.height,
+            depth: settings.depth,
+            win_length: settings.win_length,
+            order: order.clone(),
+            role: game.get_player_role(id),
+            clocks: game.clock_times(),
+        };
 
-        messages.push(serde_json::to_string(&start_mess).unwrap());
+        send_one(server, id, &serde_json::to_string(&start_mess).unwrap());
+        server.game_map.insert(id, server.games.len());
     }
-    
 
-    let game = Game::new(persons, first);
+    server.games.push(game);
+    send_playable(server, server.games.len() - 1);
+}
 
-    for id in server.lobby.iter() {
-        server.game_map.insert(*id, server.games.len());
+/// Tells the players of a game which sub-boards can be played in, if the game has them
+fn send_playable(server: &mut Server, index: usize) {
+    if let Some(boards) = server.games[index].playable_boards() {
+        let message = SendMessage::Playable {boards};
+        send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
     }
-    server.lobby.clear();
+}
 
-    server.games.push(game);
+/// Sends the marks in a quantum game to its players, for other games nothing is sent
+fn send_quantum(server: &mut Server, index: usize) {
+    let game = &server.games[index];
+    if let Some(marks) = game.quantum_marks() {
+        let message = SendMessage::Quantum {
+            marks,
+            collapse: game.pending_collapse(),
+            next: game.get_curr_player().mark(),
+        };
+        send_all(server, &serde_json::to_string(&message).unwrap(), Some(&index));
+    }
 }